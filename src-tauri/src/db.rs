@@ -0,0 +1,1157 @@
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, DatabaseName, Transaction, TransactionBehavior};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+use crate::media::{self, MediaEntry};
+
+/// Library database of a user, in their directory. In vault mode it is
+/// encrypted by SQLCipher with the vault key.
+const DATABASE_FILE: &str = "library.db";
+
+/// Schema migrations, applied in order when the database is opened.
+/// `PRAGMA user_version` counts the applied ones: never edit a released
+/// migration, append a new one.
+const MIGRATIONS: &[&str] = &[
+    // 1: entries, metadata with its genres and people, user state
+    r#"
+    CREATE TABLE entries (
+        path TEXT PRIMARY KEY,
+        id TEXT NOT NULL UNIQUE,
+        filename TEXT NOT NULL,
+        extension TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        available INTEGER NOT NULL,
+        -- The whole MediaEntry (parts, disc, subtitles...) as JSON
+        data TEXT NOT NULL
+    );
+
+    CREATE TABLE metadata (
+        media_id TEXT PRIMARY KEY,
+        media_type TEXT NOT NULL,
+        title TEXT NOT NULL,
+        year INTEGER,
+        rating REAL,
+        tmdb_id INTEGER,
+        provider TEXT NOT NULL,
+        fetched_at TEXT NOT NULL,
+        -- The whole VideoMetadata as JSON
+        data TEXT NOT NULL
+    );
+    CREATE INDEX metadata_tmdb_id ON metadata (media_type, tmdb_id);
+
+    CREATE TABLE genres (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+    );
+    CREATE TABLE metadata_genres (
+        media_id TEXT NOT NULL REFERENCES metadata (media_id)
+            ON UPDATE CASCADE ON DELETE CASCADE,
+        genre_id INTEGER NOT NULL REFERENCES genres (id),
+        PRIMARY KEY (media_id, genre_id)
+    );
+    CREATE INDEX metadata_genres_genre ON metadata_genres (genre_id);
+
+    CREATE TABLE people (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        profile_path TEXT
+    );
+    -- Cast (with their character) and crew (with their job), in billing order
+    CREATE TABLE credits (
+        media_id TEXT NOT NULL REFERENCES metadata (media_id)
+            ON UPDATE CASCADE ON DELETE CASCADE,
+        person_id INTEGER NOT NULL REFERENCES people (id),
+        role TEXT NOT NULL CHECK (role IN ('cast', 'crew')),
+        position INTEGER NOT NULL,
+        character TEXT,
+        job TEXT,
+        PRIMARY KEY (media_id, role, position)
+    );
+    CREATE INDEX credits_person ON credits (person_id);
+
+    -- Version of a title picked by the user (see `versions`)
+    CREATE TABLE version_pins (
+        title_key TEXT PRIMARY KEY,
+        media_id TEXT NOT NULL
+    );
+    -- Metadata of entries gone from the library, with the Unix time they went missing
+    CREATE TABLE metadata_orphans (
+        media_id TEXT PRIMARY KEY,
+        since INTEGER NOT NULL
+    );
+    "#,
+    // 2: when entries joined the library, kept when their file is renamed or moved
+    r#"
+    ALTER TABLE entries ADD COLUMN added_at INTEGER NOT NULL DEFAULT 0;
+    UPDATE entries SET added_at = CAST(strftime('%s', 'now') AS INTEGER);
+    "#,
+    // 3: full-text index of the metadata, kept in sync by triggers
+    r#"
+    CREATE VIRTUAL TABLE metadata_search USING fts5 (
+        media_id UNINDEXED,
+        title,
+        original_title,
+        episode_title,
+        people,
+        studios,
+        overview,
+        tagline,
+        -- Case and accents folded ("amelie" finds "Amélie"), prefixes indexed
+        tokenize = 'unicode61 remove_diacritics 2',
+        prefix = '2 3'
+    );
+
+    CREATE VIEW metadata_search_rows AS
+    SELECT
+        media_id,
+        title,
+        json_extract(data, '$.original_title'),
+        json_extract(data, '$.episode_title'),
+        (SELECT group_concat(json_extract(value, '$.name'), ' ') FROM (
+            SELECT value FROM json_each(data, '$.cast')
+            UNION ALL
+            SELECT value FROM json_each(data, '$.crew')
+        )),
+        (SELECT group_concat(value, ' ') FROM json_each(data, '$.studios')),
+        json_extract(data, '$.overview'),
+        json_extract(data, '$.tagline')
+    FROM metadata;
+
+    CREATE TRIGGER metadata_search_insert AFTER INSERT ON metadata BEGIN
+        INSERT INTO metadata_search
+        SELECT * FROM metadata_search_rows WHERE media_id = new.media_id;
+    END;
+    CREATE TRIGGER metadata_search_update AFTER UPDATE ON metadata BEGIN
+        DELETE FROM metadata_search WHERE media_id = old.media_id;
+        INSERT INTO metadata_search
+        SELECT * FROM metadata_search_rows WHERE media_id = new.media_id;
+    END;
+    CREATE TRIGGER metadata_search_delete AFTER DELETE ON metadata BEGIN
+        DELETE FROM metadata_search WHERE media_id = old.media_id;
+    END;
+
+    INSERT INTO metadata_search SELECT * FROM metadata_search_rows;
+    "#,
+    // 4: when entries were last found by a scan or the watcher
+    r#"
+    ALTER TABLE entries ADD COLUMN last_seen_at INTEGER NOT NULL DEFAULT 0;
+    UPDATE entries SET last_seen_at = added_at;
+    "#,
+];
+
+/// Bumped by every write (and reopening), so caches built from the database
+/// know when to rebuild
+static REVISION: AtomicU64 = AtomicU64::new(0);
+
+/// Connection to a user's database, emptied when it is closed under the feet of
+/// a thread still holding it
+type Slot = Arc<Mutex<Option<Connection>>>;
+
+/// Open library databases, by user
+pub struct DatabaseState {
+    connections: HashMap<String, Slot>,
+}
+
+impl DatabaseState {
+    pub fn new() -> Self {
+        Self {
+            connections: HashMap::new(),
+        }
+    }
+}
+
+fn get_database_path(app: &AppHandle, user_id: &str) -> Result<PathBuf, String> {
+    Ok(media::get_user_dir_public(app, user_id)?.join(DATABASE_FILE))
+}
+
+/// Whether a file of the user directory belongs to the database (journal included)
+pub fn is_database_file(name: &str) -> bool {
+    name.starts_with(DATABASE_FILE)
+}
+
+/// SQLCipher raw key literal ("x'…'"), or an empty key for a plaintext database
+fn key_literal(key: Option<&[u8; 32]>) -> String {
+    match key {
+        Some(key) => {
+            let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("x'{}'", hex)
+        }
+        None => String::new(),
+    }
+}
+
+/// Open a database file with its key (None: plaintext). A wrong key only shows
+/// on the first read, which is done here.
+fn open_file(path: &Path, key: Option<&[u8; 32]>) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    if key.is_some() {
+        conn.pragma_update(None, "key", key_literal(key))?;
+    }
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(conn)
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let applied: usize = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if applied > MIGRATIONS.len() {
+        return Err("The library database was created by a newer version".to_string());
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch(migration)
+            .and_then(|_| tx.pragma_update(None, "user_version", index + 1))
+            .map_err(|e| format!("Migration {} failed: {}", index + 1, e))?;
+        tx.commit().map_err(|e| e.to_string())?;
+        log::info!("[Database] Applied migration {}", index + 1);
+    }
+    Ok(())
+}
+
+/// The user's database, opened, migrated and filled from the JSON files of
+/// older versions on first use
+fn connection(app: &AppHandle, user_id: &str) -> Result<Slot, String> {
+    let state = app.state::<Arc<Mutex<DatabaseState>>>();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(slot) = state.connections.get(user_id) {
+        return Ok(slot.clone());
+    }
+    // Threads outliving a logout must not open the database again
+    if !crate::session::is_active(app, user_id) {
+        return Err("Not logged in".to_string());
+    }
+
+    let path = get_database_path(app, user_id)?;
+    let key = crate::vault::database_key(app, user_id)?;
+    let mut conn = match open_file(&path, key.as_ref()) {
+        Ok(conn) => conn,
+        Err(_) if path.exists() => {
+            // The vault mode was switched but the database not converted yet
+            let previous = crate::vault::previous_database_key(app, user_id);
+            switch_key(&path, previous.as_ref(), key.as_ref())?;
+            log::warn!("[Database] Finished an interrupted vault mode switch");
+            open_file(&path, key.as_ref())
+                .map_err(|e| format!("Failed to open library database: {}", e))?
+        }
+        Err(e) => return Err(format!("Failed to open library database: {}", e)),
+    };
+    migrate(&mut conn)?;
+    import_json(app, user_id, &mut conn)?;
+
+    let slot = Arc::new(Mutex::new(Some(conn)));
+    state.connections.insert(user_id.to_string(), slot.clone());
+    REVISION.fetch_add(1, Ordering::SeqCst);
+    log::info!("[Database] Opened library database of user {}", user_id);
+    Ok(slot)
+}
+
+/// Run `f` on the user's database
+fn read<T>(
+    app: &AppHandle,
+    user_id: &str,
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let slot = connection(app, user_id)?;
+    let conn = slot.lock().unwrap_or_else(|e| e.into_inner());
+    let conn = conn
+        .as_ref()
+        .ok_or_else(|| "Library database is closed".to_string())?;
+    f(conn).map_err(|e| e.to_string())
+}
+
+/// Run `f` in a write transaction, committed only if it succeeds: scans, the
+/// watcher and metadata fetches never see (or overwrite) half of another's changes
+fn write<T>(
+    app: &AppHandle,
+    user_id: &str,
+    f: impl FnOnce(&Transaction) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let slot = connection(app, user_id)?;
+    let mut conn = slot.lock().unwrap_or_else(|e| e.into_inner());
+    let conn = conn
+        .as_mut()
+        .ok_or_else(|| "Library database is closed".to_string())?;
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|e| e.to_string())?;
+    let value = f(&tx).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    REVISION.fetch_add(1, Ordering::SeqCst);
+    Ok(value)
+}
+
+/// Current revision of the databases: changes after any write
+pub fn revision() -> u64 {
+    REVISION.load(Ordering::SeqCst)
+}
+
+/// Close the user's database (vault locked, profile deleted), dropping what
+/// was cached from it
+pub fn close(app: &AppHandle, user_id: &str) {
+    let state = app.state::<Arc<Mutex<DatabaseState>>>();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(slot) = state.connections.remove(user_id) {
+        // Waits for the running query, if any
+        *slot.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        log::info!("[Database] Closed library database of user {}", user_id);
+    }
+    crate::library::forget(app, user_id);
+}
+
+/// Re-encrypt a database file from `old_key` to `new_key` (None: plaintext).
+/// A file already using `new_key` is left alone.
+fn switch_key(
+    path: &Path,
+    old_key: Option<&[u8; 32]>,
+    new_key: Option<&[u8; 32]>,
+) -> Result<(), String> {
+    let conn = match open_file(path, old_key) {
+        Ok(conn) => conn,
+        Err(_) if open_file(path, new_key).is_ok() => return Ok(()),
+        Err(e) => return Err(format!("Failed to open library database: {}", e)),
+    };
+
+    if old_key.is_some() && new_key.is_some() {
+        return conn
+            .pragma_update(None, "rekey", key_literal(new_key))
+            .map_err(|e| e.to_string());
+    }
+
+    // Between plaintext and encrypted, SQLCipher copies the database to a new file
+    let converted = path.with_extension("db.tmp");
+    let _ = fs::remove_file(&converted);
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS converted KEY ?2",
+        params![converted.to_string_lossy(), key_literal(new_key)],
+    )
+    .and_then(|_| conn.query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(())))
+    .and_then(|_| {
+        conn.pragma_update(
+            Some(DatabaseName::Attached("converted")),
+            "user_version",
+            version,
+        )
+    })
+    .and_then(|_| conn.execute_batch("DETACH DATABASE converted"))
+    .map_err(|e| format!("Failed to convert library database: {}", e))?;
+    drop(conn);
+    fs::rename(&converted, path).map_err(|e| e.to_string())
+}
+
+/// Move the user's database to a new key after the vault mode or the PIN changed.
+/// The open connection is reopened with the new key, and no other can be opened
+/// meanwhile.
+pub fn rekey(
+    app: &AppHandle,
+    user_id: &str,
+    old_key: Option<&[u8; 32]>,
+    new_key: Option<&[u8; 32]>,
+) -> Result<(), String> {
+    let path = get_database_path(app, user_id)?;
+    if old_key == new_key || !path.exists() {
+        return Ok(());
+    }
+
+    let state = app.state::<Arc<Mutex<DatabaseState>>>();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    let slot = state.connections.get(user_id).cloned();
+    let mut conn = slot
+        .as_ref()
+        .map(|slot| slot.lock().unwrap_or_else(|e| e.into_inner()));
+    // Files cannot be replaced while open on Windows
+    if let Some(conn) = conn.as_mut() {
+        **conn = None;
+    }
+
+    let result = switch_key(&path, old_key, new_key);
+    if let Some(conn) = conn.as_mut() {
+        let key = if result.is_ok() { new_key } else { old_key };
+        **conn = open_file(&path, key).ok();
+        if conn.is_none() {
+            state.connections.remove(user_id);
+        }
+    }
+
+    match result {
+        Ok(()) => {
+            log::info!("[Database] Re-keyed library database of user {}", user_id);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn from_json<T: DeserializeOwned>(raw: &str, column: usize) -> rusqlite::Result<T> {
+    serde_json::from_str(raw)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(e)))
+}
+
+// ─── Entries ────────────────────────────────────────────────────────────────
+
+/// Write an entry. Its `added_at` and `last_seen_at` columns win over the
+/// JSON: a file renamed or moved (same ID) or rewritten in place (same path)
+/// keeps the date it was added.
+fn insert_entry(conn: &Connection, entry: &MediaEntry) -> rusqlite::Result<()> {
+    let (added_at, last_seen_at): (Option<i64>, Option<i64>) = conn
+        .prepare_cached(
+            "SELECT min(added_at), max(last_seen_at) FROM entries WHERE path = ?1 OR id = ?2",
+        )?
+        .query_row(params![entry.path, entry.id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    // Entries of older versions have no date
+    let added_at = added_at
+        .or(Some(entry.added_at).filter(|at| *at > 0))
+        .unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let last_seen_at = entry
+        .last_seen_at
+        .max(last_seen_at.unwrap_or(0))
+        .max(added_at);
+
+    // REPLACE also drops a row holding the same ID under another path
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO entries
+            (path, id, filename, extension, size_bytes, available, data, added_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?
+    .execute(params![
+        entry.path,
+        entry.id,
+        entry.filename,
+        entry.extension,
+        entry.size_bytes,
+        entry.available,
+        to_json(entry)?,
+        added_at,
+        last_seen_at,
+    ])?;
+    Ok(())
+}
+
+/// Every library entry, by path
+pub fn load_entries(app: &AppHandle, user_id: &str) -> Result<Vec<MediaEntry>, String> {
+    read(app, user_id, |conn| {
+        let mut stmt =
+            conn.prepare("SELECT data, added_at, last_seen_at FROM entries ORDER BY path")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(MediaEntry {
+                added_at: row.get(1)?,
+                last_seen_at: row.get(2)?,
+                ..from_json(&row.get::<_, String>(0)?, 0)?
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Mark every available entry as seen at `at`, once a scan went over all of them
+pub fn mark_seen(app: &AppHandle, user_id: &str, at: i64) -> Result<(), String> {
+    write(app, user_id, |tx| {
+        tx.execute(
+            "UPDATE entries SET last_seen_at = ?1 WHERE available = 1",
+            [at],
+        )?;
+        Ok(())
+    })
+}
+
+/// Remove the entries at `removed` paths and write `upserted` ones (replacing the
+/// entry at the same path), in one transaction
+pub fn update_entries(
+    app: &AppHandle,
+    user_id: &str,
+    removed: &[&str],
+    upserted: &[&MediaEntry],
+) -> Result<(), String> {
+    write(app, user_id, |tx| {
+        // Upserts first: a moved file still finds its old row
+        for entry in upserted {
+            insert_entry(tx, entry)?;
+        }
+        let mut delete = tx.prepare_cached("DELETE FROM entries WHERE path = ?1")?;
+        for path in removed {
+            delete.execute([path])?;
+        }
+        Ok(())
+    })
+}
+
+// ─── Metadata ───────────────────────────────────────────────────────────────
+
+/// Write the metadata of an entry, with its genres and credits
+fn insert_metadata(
+    conn: &Connection,
+    media_id: &str,
+    meta: &serde_json::Value,
+) -> rusqlite::Result<()> {
+    let text = |key: &str| meta.get(key).and_then(|v| v.as_str());
+    let list = |key: &str| {
+        meta.get(key)
+            .and_then(|v| v.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    };
+
+    conn.prepare_cached(
+        "INSERT INTO metadata (media_id, media_type, title, year, rating, tmdb_id, provider, fetched_at, data)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (media_id) DO UPDATE SET
+            media_type = excluded.media_type, title = excluded.title, year = excluded.year,
+            rating = excluded.rating, tmdb_id = excluded.tmdb_id, provider = excluded.provider,
+            fetched_at = excluded.fetched_at, data = excluded.data",
+    )?
+    .execute(params![
+        media_id,
+        text("media_type").unwrap_or("unknown"),
+        text("title").unwrap_or_default(),
+        meta.get("year").and_then(|v| v.as_u64()),
+        meta.get("rating").and_then(|v| v.as_f64()),
+        meta.get("tmdb_id").and_then(|v| v.as_u64()),
+        text("provider").unwrap_or("local"),
+        text("fetched_at").unwrap_or_default(),
+        to_json(meta)?,
+    ])?;
+
+    conn.prepare_cached("DELETE FROM metadata_genres WHERE media_id = ?1")?
+        .execute([media_id])?;
+    for genre in list("genres").iter().filter_map(|g| g.as_str()) {
+        let genre_id: i64 = conn
+            .prepare_cached(
+                "INSERT INTO genres (name) VALUES (?1)
+                 ON CONFLICT (name) DO UPDATE SET name = genres.name RETURNING id",
+            )?
+            .query_row([genre], |row| row.get(0))?;
+        conn.prepare_cached(
+            "INSERT OR IGNORE INTO metadata_genres (media_id, genre_id) VALUES (?1, ?2)",
+        )?
+        .execute(params![media_id, genre_id])?;
+    }
+
+    conn.prepare_cached("DELETE FROM credits WHERE media_id = ?1")?
+        .execute([media_id])?;
+    for (role, detail_key) in [("cast", "character"), ("crew", "job")] {
+        for (position, member) in list(role).iter().enumerate() {
+            let name = match member.get("name").and_then(|n| n.as_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            let profile_path = member.get("profile_path").and_then(|p| p.as_str());
+            let detail = member.get(detail_key).and_then(|d| d.as_str());
+            let (character, job) = if role == "cast" {
+                (detail, None)
+            } else {
+                (None, detail)
+            };
+
+            let person_id: i64 = conn
+                .prepare_cached(
+                    "INSERT INTO people (name, profile_path) VALUES (?1, ?2)
+                     ON CONFLICT (name) DO UPDATE SET
+                        profile_path = coalesce(excluded.profile_path, people.profile_path)
+                     RETURNING id",
+                )?
+                .query_row(params![name, profile_path], |row| row.get(0))?;
+            conn.prepare_cached(
+                "INSERT INTO credits (media_id, person_id, role, position, character, job)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?
+            .execute(params![media_id, person_id, role, position, character, job])?;
+        }
+    }
+    Ok(())
+}
+
+/// Metadata of an entry, None when it has none yet
+pub fn load_metadata(
+    app: &AppHandle,
+    user_id: &str,
+    media_id: &str,
+) -> Result<Option<serde_json::Value>, String> {
+    read(app, user_id, |conn| {
+        let mut stmt = conn.prepare_cached("SELECT data FROM metadata WHERE media_id = ?1")?;
+        let mut rows = stmt.query([media_id])?;
+        match rows.next()? {
+            Some(row) => from_json(&row.get::<_, String>(0)?, 0).map(Some),
+            None => Ok(None),
+        }
+    })
+}
+
+/// Metadata of every entry that has some, by media ID
+pub fn load_all_metadata(
+    app: &AppHandle,
+    user_id: &str,
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    read(app, user_id, |conn| {
+        let mut stmt = conn.prepare("SELECT media_id, data FROM metadata")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get(0)?, from_json(&row.get::<_, String>(1)?, 1)?))
+        })?;
+        rows.collect()
+    })
+}
+
+/// IDs of the entries that have metadata
+pub fn metadata_ids(app: &AppHandle, user_id: &str) -> Result<Vec<String>, String> {
+    read(app, user_id, |conn| {
+        let mut stmt = conn.prepare("SELECT media_id FROM metadata")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    })
+}
+
+pub fn save_metadata(
+    app: &AppHandle,
+    user_id: &str,
+    media_id: &str,
+    meta: &serde_json::Value,
+) -> Result<(), String> {
+    write(app, user_id, |tx| insert_metadata(tx, media_id, meta))
+}
+
+/// Delete the metadata of entries, and the genres and people no longer credited.
+/// Returns how many entries had metadata.
+pub fn delete_metadata(
+    app: &AppHandle,
+    user_id: &str,
+    media_ids: &[&str],
+) -> Result<usize, String> {
+    write(app, user_id, |tx| {
+        let mut deleted = 0;
+        let mut delete = tx.prepare_cached("DELETE FROM metadata WHERE media_id = ?1")?;
+        for media_id in media_ids {
+            deleted += delete.execute([media_id])?;
+        }
+        if deleted > 0 {
+            tx.execute_batch(
+                "DELETE FROM genres WHERE id NOT IN (SELECT genre_id FROM metadata_genres);
+                 DELETE FROM people WHERE id NOT IN (SELECT person_id FROM credits);",
+            )?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Give the metadata of `from` to `to`, unless `to` already has some.
+/// Returns whether it was moved.
+pub fn move_metadata(app: &AppHandle, user_id: &str, from: &str, to: &str) -> Result<bool, String> {
+    write(app, user_id, |tx| {
+        let taken: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM metadata WHERE media_id = ?1)",
+            [to],
+            |row| row.get(0),
+        )?;
+        if taken {
+            return Ok(false);
+        }
+        let moved = tx.execute(
+            "UPDATE metadata SET media_id = ?2 WHERE media_id = ?1",
+            [from, to],
+        )?;
+        Ok(moved > 0)
+    })
+}
+
+/// Full-text query matching every word of `text` as a word prefix, None
+/// when it has no word
+fn match_expression(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| format!("\"{}\"*", w))
+        .collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// Entries whose metadata matches `text`, with their relevance (lower is better).
+/// Titles weigh most, then people and studios, then overviews.
+pub fn search_metadata(
+    app: &AppHandle,
+    user_id: &str,
+    text: &str,
+) -> Result<HashMap<String, f64>, String> {
+    let expression = match match_expression(text) {
+        Some(expression) => expression,
+        None => return Ok(HashMap::new()),
+    };
+    read(app, user_id, |conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT media_id, bm25(metadata_search, 0, 10, 8, 5, 3, 2, 1, 1)
+             FROM metadata_search WHERE metadata_search MATCH ?1",
+        )?;
+        let rows = stmt.query_map([expression], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    })
+}
+
+// ─── User state ─────────────────────────────────────────────────────────────
+
+/// Versions picked by the user, by title key
+pub fn load_version_pins(
+    app: &AppHandle,
+    user_id: &str,
+) -> Result<HashMap<String, String>, String> {
+    read(app, user_id, |conn| {
+        let mut stmt = conn.prepare("SELECT title_key, media_id FROM version_pins")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    })
+}
+
+pub fn set_version_pin(
+    app: &AppHandle,
+    user_id: &str,
+    title_key: &str,
+    media_id: &str,
+) -> Result<(), String> {
+    write(app, user_id, |tx| {
+        tx.execute(
+            "INSERT OR REPLACE INTO version_pins (title_key, media_id) VALUES (?1, ?2)",
+            [title_key, media_id],
+        )?;
+        Ok(())
+    })
+}
+
+/// Orphaned media IDs with the Unix time they were first found missing
+pub fn load_orphans(app: &AppHandle, user_id: &str) -> Result<HashMap<String, i64>, String> {
+    read(app, user_id, |conn| {
+        let mut stmt = conn.prepare("SELECT media_id, since FROM metadata_orphans")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    })
+}
+
+fn insert_orphans(conn: &Connection, orphans: &HashMap<String, i64>) -> rusqlite::Result<()> {
+    let mut insert = conn.prepare_cached(
+        "INSERT OR REPLACE INTO metadata_orphans (media_id, since) VALUES (?1, ?2)",
+    )?;
+    for (media_id, since) in orphans {
+        insert.execute(params![media_id, since])?;
+    }
+    Ok(())
+}
+
+pub fn save_orphans(
+    app: &AppHandle,
+    user_id: &str,
+    orphans: &HashMap<String, i64>,
+) -> Result<(), String> {
+    write(app, user_id, |tx| {
+        tx.execute("DELETE FROM metadata_orphans", [])?;
+        insert_orphans(tx, orphans)
+    })
+}
+
+// ─── JSON layout of older versions ──────────────────────────────────────────
+
+/// Files the library was stored in before the database
+fn legacy_files(user_dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = ["library.json", "versions.json", "metadata_gc.json"]
+        .iter()
+        .map(|name| user_dir.join(name))
+        .collect();
+    if let Ok(entries) = fs::read_dir(user_dir.join("metas")) {
+        for entry in entries.flatten() {
+            files.push(entry.path().join("meta.json"));
+        }
+    }
+    files.into_iter().filter(|p| p.is_file()).collect()
+}
+
+/// Reads a file of the user directory (through the vault)
+type ReadFile<'a> = dyn Fn(&Path) -> Result<String, String> + 'a;
+
+/// Read a JSON file of the old layout
+fn read_legacy<T: DeserializeOwned>(read: &ReadFile, path: &Path) -> Result<T, String> {
+    let raw = read(path)?;
+    serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Fill an empty database from the JSON files of older versions (or of an
+/// imported profile bundle), then delete them
+fn import_json(app: &AppHandle, user_id: &str, conn: &mut Connection) -> Result<(), String> {
+    let user_dir = media::get_user_dir_public(app, user_id)?;
+    let read = |path: &Path| crate::vault::read_to_string(app, user_id, path);
+    import_legacy(conn, user_id, &user_dir, &read)
+}
+
+/// Import of the JSON files found in `user_dir`. The library file must be
+/// readable; unreadable metadata is only logged, it can be fetched again.
+fn import_legacy(
+    conn: &mut Connection,
+    user_id: &str,
+    user_dir: &Path,
+    read: &ReadFile,
+) -> Result<(), String> {
+    let files = legacy_files(user_dir);
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let empty: bool = conn
+        .query_row(
+            "SELECT NOT EXISTS (SELECT 1 FROM entries) AND NOT EXISTS (SELECT 1 FROM metadata)",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !empty {
+        // Left over by an import interrupted after its commit
+        log::warn!(
+            "[Database] Library database of user {} already filled, deleting {} JSON file(s)",
+            user_id,
+            files.len()
+        );
+    } else {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let (mut entries, mut metadata) = (0, 0);
+        for path in &files {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            let imported = match name {
+                "library.json" => {
+                    let library: Vec<MediaEntry> = read_legacy(read, path)?;
+                    entries = library.len();
+                    library
+                        .iter()
+                        .try_for_each(|entry| insert_entry(&tx, entry))
+                }
+                "versions.json" => match read_legacy::<HashMap<String, String>>(read, path) {
+                    Ok(pins) => pins.iter().try_for_each(|(key, id)| {
+                        tx.execute(
+                            "INSERT OR REPLACE INTO version_pins (title_key, media_id) VALUES (?1, ?2)",
+                            [key, id],
+                        )
+                        .map(|_| ())
+                    }),
+                    Err(e) => {
+                        log::warn!("[Database] Skipping version picks: {}", e);
+                        Ok(())
+                    }
+                },
+                "metadata_gc.json" => match read_legacy(read, path) {
+                    Ok(orphans) => insert_orphans(&tx, &orphans),
+                    Err(e) => {
+                        log::warn!("[Database] Skipping metadata orphans: {}", e);
+                        Ok(())
+                    }
+                },
+                _ => {
+                    let media_id = path
+                        .parent()
+                        .and_then(|dir| dir.file_name())
+                        .map(|id| id.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    match read_legacy(read, path) {
+                        Ok(meta) => {
+                            metadata += 1;
+                            insert_metadata(&tx, &media_id, &meta)
+                        }
+                        Err(e) => {
+                            log::warn!("[Database] Skipping metadata: {}", e);
+                            Ok(())
+                        }
+                    }
+                }
+            };
+            imported.map_err(|e| format!("Failed to import {}: {}", path.display(), e))?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        log::info!(
+            "[Database] Imported {} entries and {} metadata from JSON files for user {}",
+            entries,
+            metadata,
+            user_id
+        );
+    }
+
+    for path in files {
+        if let Err(e) = fs::remove_file(&path) {
+            log::warn!("[Database] Failed to delete {}: {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// The library in the JSON layout of older versions, as (path relative to the
+/// user directory, contents): profile bundles keep this layout, so their paths
+/// can be remapped and any version can import them
+pub fn export_json(app: &AppHandle, user_id: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = vec![(
+        "library.json".to_string(),
+        serde_json::to_vec_pretty(&load_entries(app, user_id)?).map_err(|e| e.to_string())?,
+    )];
+    for (media_id, meta) in load_all_metadata(app, user_id)? {
+        files.push((
+            format!("metas/{}/meta.json", media_id),
+            serde_json::to_vec_pretty(&meta).map_err(|e| e.to_string())?,
+        ));
+    }
+    files.push((
+        "versions.json".to_string(),
+        serde_json::to_vec_pretty(&load_version_pins(app, user_id)?).map_err(|e| e.to_string())?,
+    ));
+    files.push((
+        "metadata_gc.json".to_string(),
+        serde_json::to_vec(&load_orphans(app, user_id)?).map_err(|e| e.to_string())?,
+    ));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::testing::TempDir;
+    use crate::media::MediaWithMetadata;
+    use serde_json::json;
+
+    fn migrated() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn
+    }
+
+    fn entry(path: &str, id: &str) -> MediaEntry {
+        MediaEntry {
+            id: id.to_string(),
+            ..MediaWithMetadata::test(path).entry
+        }
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    fn search(conn: &Connection, text: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT media_id FROM metadata_search WHERE metadata_search MATCH ?1")
+            .unwrap();
+        let rows = stmt
+            .query_map([match_expression(text).unwrap()], |row| row.get(0))
+            .unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    fn read_file(path: &Path) -> Result<String, String> {
+        fs::read_to_string(path).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn migrations_bring_a_new_database_to_the_latest_version() {
+        let mut conn = migrated();
+        assert_eq!(count(&conn, "PRAGMA user_version"), MIGRATIONS.len() as i64);
+        for table in [
+            "entries",
+            "metadata",
+            "credits",
+            "version_pins",
+            "metadata_search",
+        ] {
+            let sql = format!("SELECT count(*) FROM {}", table);
+            assert_eq!(count(&conn, &sql), 0, "{}", table);
+        }
+
+        // Opening again applies nothing
+        migrate(&mut conn).unwrap();
+        assert_eq!(count(&conn, "PRAGMA user_version"), MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn migrations_refuse_a_newer_database() {
+        let mut conn = migrated();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(migrate(&mut conn).is_err());
+    }
+
+    #[test]
+    fn migrations_fill_new_columns_and_the_search_index() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute(
+            "INSERT INTO entries (path, id, filename, extension, size_bytes, available, data)
+             VALUES ('/m/a.mkv', 'a', 'a.mkv', 'mkv', 1, 1, '{}')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO metadata (media_id, media_type, title, provider, fetched_at, data)
+             VALUES ('a', 'movie', 'Amélie', 'tmdb', '', '{\"overview\": \"Paris\"}')",
+            [],
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+
+        let (added_at, last_seen_at): (i64, i64) = conn
+            .query_row("SELECT added_at, last_seen_at FROM entries", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert!(added_at > 0);
+        assert_eq!(last_seen_at, added_at);
+        assert_eq!(search(&conn, "amelie"), vec!["a"]);
+        assert_eq!(search(&conn, "par"), vec!["a"]);
+    }
+
+    #[test]
+    fn search_index_follows_metadata_changes() {
+        let conn = migrated();
+        let meta = json!({
+            "title": "Heat",
+            "cast": [{"name": "Al Pacino", "character": "Vincent Hanna"}],
+            "studios": ["Regency"],
+        });
+        insert_metadata(&conn, "a", &meta).unwrap();
+        assert_eq!(search(&conn, "pacino"), vec!["a"]);
+        assert_eq!(search(&conn, "regency heat"), vec!["a"]);
+
+        insert_metadata(&conn, "a", &json!({"title": "Ronin"})).unwrap();
+        assert!(search(&conn, "heat").is_empty());
+        assert_eq!(search(&conn, "ronin"), vec!["a"]);
+
+        conn.execute("DELETE FROM metadata WHERE media_id = 'a'", [])
+            .unwrap();
+        assert!(search(&conn, "ronin").is_empty());
+    }
+
+    #[test]
+    fn moved_and_rewritten_entries_keep_their_added_date() {
+        let conn = migrated();
+        let first = MediaEntry {
+            added_at: 100,
+            last_seen_at: 200,
+            ..entry("/m/old.mkv", "a")
+        };
+        insert_entry(&conn, &first).unwrap();
+
+        // Same content under another path: the row moves
+        let moved = MediaEntry {
+            added_at: 500,
+            last_seen_at: 500,
+            ..entry("/m/new.mkv", "a")
+        };
+        insert_entry(&conn, &moved).unwrap();
+        // Same path with new content
+        let rewritten = MediaEntry {
+            added_at: 600,
+            last_seen_at: 150,
+            ..entry("/m/new.mkv", "b")
+        };
+        insert_entry(&conn, &rewritten).unwrap();
+
+        let rows: Vec<(String, String, i64, i64)> = conn
+            .prepare("SELECT path, id, added_at, last_seen_at FROM entries")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![("/m/new.mkv".to_string(), "b".to_string(), 100, 500)]
+        );
+    }
+
+    #[test]
+    fn import_fills_an_empty_database_and_deletes_the_json_files() {
+        let dir = TempDir::new();
+        let entries = vec![entry("/m/a.mkv", "a"), entry("/m/b.mkv", "b")];
+        dir.write("library.json", serde_json::to_string(&entries).unwrap());
+        dir.write("versions.json", json!({"heat|1995": "b"}).to_string());
+        dir.write("metadata_gc.json", json!({"gone": 1234}).to_string());
+        dir.write(
+            "metas/a/meta.json",
+            json!({"title": "Heat", "genres": ["Crime"], "provider": "tmdb"}).to_string(),
+        );
+        dir.write("metas/b/meta.json", "{ truncated");
+
+        let mut conn = migrated();
+        import_legacy(&mut conn, "user", dir.path(), &read_file).unwrap();
+
+        assert_eq!(count(&conn, "SELECT count(*) FROM entries"), 2);
+        assert_eq!(count(&conn, "SELECT count(*) FROM metadata"), 1);
+        assert_eq!(count(&conn, "SELECT count(*) FROM metadata_genres"), 1);
+        assert_eq!(count(&conn, "SELECT count(*) FROM version_pins"), 1);
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT since FROM metadata_orphans WHERE media_id = 'gone'"
+            ),
+            1234
+        );
+        assert_eq!(search(&conn, "heat"), vec!["a"]);
+        assert!(legacy_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn import_fails_on_an_unreadable_library_and_keeps_the_files() {
+        let dir = TempDir::new();
+        dir.write("library.json", json!({"not": "a list"}).to_string());
+        dir.write("metas/a/meta.json", json!({"title": "Heat"}).to_string());
+
+        let mut conn = migrated();
+        assert!(import_legacy(&mut conn, "user", dir.path(), &read_file).is_err());
+        assert_eq!(count(&conn, "SELECT count(*) FROM metadata"), 0);
+        assert_eq!(legacy_files(dir.path()).len(), 2);
+    }
+
+    #[test]
+    fn import_only_deletes_the_files_left_next_to_a_filled_database() {
+        let dir = TempDir::new();
+        dir.write(
+            "library.json",
+            serde_json::to_string(&vec![entry("/m/b.mkv", "b")]).unwrap(),
+        );
+
+        let mut conn = migrated();
+        insert_entry(&conn, &entry("/m/a.mkv", "a")).unwrap();
+        import_legacy(&mut conn, "user", dir.path(), &read_file).unwrap();
+
+        let ids: Vec<String> = conn
+            .prepare("SELECT id FROM entries")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(ids, vec!["a"]);
+        assert!(legacy_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn match_expression_quotes_word_prefixes() {
+        assert_eq!(
+            match_expression("Amélie, l'\"OR"),
+            Some("\"Amélie\"* \"l\"* \"OR\"*".to_string())
+        );
+        assert_eq!(match_expression(" -- "), None);
+    }
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Disc structure a `MediaEntry` was built from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscFormat {
+    /// Folder holding a BDMV structure
+    Bluray,
+    /// Folder holding a VIDEO_TS structure
+    Dvd,
+    /// Disc image file
+    Iso,
+}
+
+impl DiscFormat {
+    /// Name stored as the entry extension (shown as its container)
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DiscFormat::Bluray => "bluray",
+            DiscFormat::Dvd => "dvd",
+            DiscFormat::Iso => "iso",
+        }
+    }
+}
+
+/// Disc details of an entry standing for a whole disc
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DiscInfo {
+    pub format: DiscFormat,
+    /// Files of the main feature, in playback order
+    pub main_feature: Vec<String>,
+}
+
+/// Folders of a disc structure, never scanned as separate titles
+const DISC_FOLDERS: &[&str] = &["BDMV", "VIDEO_TS", "AUDIO_TS", "CERTIFICATE"];
+
+/// Whether a folder name is part of a disc structure
+pub fn is_disc_folder(name: &str) -> bool {
+    DISC_FOLDERS.iter().any(|f| f.eq_ignore_ascii_case(name))
+}
+
+/// Disc format of a folder, from the names of its subdirectories
+pub fn disc_format(subdirs: &[String]) -> Option<DiscFormat> {
+    if subdirs.iter().any(|d| d.eq_ignore_ascii_case("BDMV")) {
+        Some(DiscFormat::Bluray)
+    } else if subdirs.iter().any(|d| d.eq_ignore_ascii_case("VIDEO_TS")) {
+        Some(DiscFormat::Dvd)
+    } else {
+        None
+    }
+}
+
+/// Disc format of a folder, reading its subdirectories
+pub fn disc_format_of(dir: &Path) -> Option<DiscFormat> {
+    let subdirs: Vec<String> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    disc_format(&subdirs)
+}
+
+/// Folder holding the disc structure `path` is part of (the parent of its BDMV or VIDEO_TS)
+pub fn disc_root_of(path: &Path) -> Option<PathBuf> {
+    path.ancestors().skip(1).find_map(|ancestor| {
+        let name = ancestor.file_name()?.to_str()?;
+        if name.eq_ignore_ascii_case("BDMV") || name.eq_ignore_ascii_case("VIDEO_TS") {
+            ancestor.parent().map(Path::to_path_buf)
+        } else {
+            None
+        }
+    })
+}
+
+/// Child of `dir` named `name`, ignoring case (discs are often ripped from FAT/UDF)
+fn find_child(dir: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .find(|e| e.file_name().to_string_lossy().eq_ignore_ascii_case(name))
+        .map(|e| e.path())
+}
+
+/// Files of a folder with their size
+fn files_with_size(dir: &Path) -> Vec<(PathBuf, String, u64)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let meta = e.metadata().ok().filter(|m| m.is_file())?;
+            let name = e.file_name().to_string_lossy().to_uppercase();
+            Some((e.path(), name, meta.len()))
+        })
+        .collect()
+}
+
+/// Stream files of the main feature of a disc folder and their total size.
+/// Blu-ray: the largest BDMV/STREAM/*.m2ts. DVD: the title set whose VTS_xx_N.VOB
+/// files are the largest in total, in part order (VTS_xx_0.VOB is its menu).
+pub fn main_feature(root: &Path, format: DiscFormat) -> Option<(Vec<PathBuf>, u64)> {
+    match format {
+        DiscFormat::Bluray => {
+            let stream_dir = find_child(&find_child(root, "BDMV")?, "STREAM")?;
+            files_with_size(&stream_dir)
+                .into_iter()
+                .filter(|(_, name, _)| name.ends_with(".M2TS"))
+                .max_by_key(|(_, _, size)| *size)
+                .map(|(path, _, size)| (vec![path], size))
+        }
+        DiscFormat::Dvd => {
+            let video_ts = find_child(root, "VIDEO_TS")?;
+            let mut title_sets: std::collections::HashMap<String, Vec<(u8, PathBuf, u64)>> =
+                std::collections::HashMap::new();
+            for (path, name, size) in files_with_size(&video_ts) {
+                // VTS_<title set>_<part>.VOB
+                let bytes = name.as_bytes();
+                if name.len() != 12 || !name.starts_with("VTS_") || !name.ends_with(".VOB") {
+                    continue;
+                }
+                let part = bytes[7];
+                if bytes[6] != b'_' || !(b'1'..=b'9').contains(&part) {
+                    continue;
+                }
+                title_sets
+                    .entry(name[4..6].to_string())
+                    .or_default()
+                    .push((part, path, size));
+            }
+            let mut parts = title_sets
+                .into_values()
+                .max_by_key(|parts| parts.iter().map(|(_, _, size)| size).sum::<u64>())?;
+            parts.sort_by_key(|(part, _, _)| *part);
+            let total = parts.iter().map(|(_, _, size)| size).sum();
+            Some((parts.into_iter().map(|(_, path, _)| path).collect(), total))
+        }
+        DiscFormat::Iso => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::testing::TempDir;
+
+    fn file(disc: &TempDir, path: &str, size: usize) -> PathBuf {
+        disc.write(path, vec![0; size])
+    }
+
+    #[test]
+    fn dvd_main_feature_is_the_largest_title_set_in_part_order() {
+        let disc = TempDir::new();
+        file(&disc, "VIDEO_TS/VIDEO_TS.VOB", 900);
+        // The menu of a title set does not count
+        file(&disc, "VIDEO_TS/VTS_01_0.VOB", 5000);
+        file(&disc, "VIDEO_TS/VTS_01_1.VOB", 1500);
+        let second = file(&disc, "VIDEO_TS/vts_02_2.vob", 1000);
+        let first = file(&disc, "VIDEO_TS/VTS_02_1.VOB", 1000);
+        file(&disc, "VIDEO_TS/VTS_03_1.BUP", 9000);
+
+        assert_eq!(disc_format_of(disc.path()), Some(DiscFormat::Dvd));
+        assert_eq!(
+            main_feature(disc.path(), DiscFormat::Dvd),
+            Some((vec![first, second], 2000))
+        );
+    }
+
+    #[test]
+    fn bluray_main_feature_is_the_largest_stream() {
+        let disc = TempDir::new();
+        file(&disc, "BDMV/STREAM/00000.m2ts", 100);
+        let feature = file(&disc, "BDMV/STREAM/00001.m2ts", 300);
+        file(&disc, "BDMV/STREAM/00002.m2ts", 200);
+
+        assert_eq!(disc_format_of(disc.path()), Some(DiscFormat::Bluray));
+        assert_eq!(
+            main_feature(disc.path(), DiscFormat::Bluray),
+            Some((vec![feature.clone()], 300))
+        );
+        assert_eq!(disc_root_of(&feature), Some(disc.path().to_path_buf()));
+    }
+
+    #[test]
+    fn folders_without_disc_structure_have_no_format() {
+        assert_eq!(disc_format(&["Extras".to_string()]), None);
+        assert_eq!(
+            disc_format(&["video_ts".to_string(), "AUDIO_TS".to_string()]),
+            Some(DiscFormat::Dvd)
+        );
+        assert_eq!(disc_root_of(Path::new("/movies/Heat/Heat.mkv")), None);
+    }
+}
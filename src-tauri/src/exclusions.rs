@@ -0,0 +1,342 @@
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::media::{MediaConfig, MediaFolder};
+
+/// Name of the per-directory ignore file, applying to its directory and below
+pub const IGNORE_FILE_NAME: &str = ".popcornignore";
+
+/// Rules applied to every folder unless `MediaConfig::default_exclusions` is off
+const DEFAULT_EXCLUSIONS: &[&str] = &[
+    // Hidden files and folders (.Trash-1000, .@__thumb, macOS "._" resource forks)
+    ".*",
+    // Samples shipped alongside releases
+    "sample/",
+    "samples/",
+    "sample.*",
+    "*-sample.*",
+    "*.sample.*",
+    "*_sample.*",
+    // NAS and OS metadata folders
+    "@eaDir/",
+    "@Recycle/",
+    "@Recently-Snapshot/",
+    "#recycle/",
+    "#snapshot/",
+    "$RECYCLE.BIN/",
+    "System Volume Information/",
+    "lost+found/",
+];
+
+/// A single gitignore-like pattern
+struct Rule {
+    matcher: GlobMatcher,
+    /// `!pattern`: re-include what an earlier rule excluded
+    negate: bool,
+    /// `pattern/`: only matches directories
+    dir_only: bool,
+    /// Patterns containing a `/` match the path relative to the rule's base
+    /// directory, the others match the file or directory name at any depth
+    anchored: bool,
+}
+
+/// Rules coming from one source (defaults, folder settings or an ignore file)
+struct RuleSet {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+/// Ignore rules in effect for a directory: later sets take precedence, and
+/// within a set the last matching rule wins
+#[derive(Clone)]
+pub struct IgnoreRules {
+    sets: Vec<Arc<RuleSet>>,
+}
+
+fn parse_rule(line: &str) -> Result<Option<Rule>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (negate, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let anchored = pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+
+    let matcher = GlobBuilder::new(pattern)
+        .case_insensitive(true)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| format!("Invalid pattern \"{}\": {}", line, e))?
+        .compile_matcher();
+
+    Ok(Some(Rule {
+        matcher,
+        negate,
+        dir_only,
+        anchored,
+    }))
+}
+
+fn parse_rules<'a>(base: &Path, lines: impl Iterator<Item = &'a str>) -> RuleSet {
+    let mut rules = Vec::new();
+    for line in lines {
+        match parse_rule(line) {
+            Ok(Some(rule)) => rules.push(rule),
+            Ok(None) => {}
+            Err(e) => log::warn!("[Media] Ignoring rule in {}: {}", base.display(), e),
+        }
+    }
+    RuleSet {
+        base: base.to_path_buf(),
+        rules,
+    }
+}
+
+/// Check that user-provided exclusion patterns are valid globs
+pub fn validate_patterns(patterns: &[String]) -> Result<(), String> {
+    for pattern in patterns {
+        parse_rule(pattern)?;
+    }
+    Ok(())
+}
+
+fn default_rules() -> Arc<RuleSet> {
+    static DEFAULTS: OnceLock<Arc<RuleSet>> = OnceLock::new();
+    DEFAULTS
+        .get_or_init(|| {
+            Arc::new(parse_rules(
+                Path::new(""),
+                DEFAULT_EXCLUSIONS.iter().copied(),
+            ))
+        })
+        .clone()
+}
+
+impl IgnoreRules {
+    /// Rules for the root of a library folder: built-in defaults and the
+    /// folder's own exclusions. Ignore files are added with `enter`.
+    pub fn for_folder(folder: &MediaFolder, default_exclusions: bool) -> Self {
+        let root = Path::new(&folder.path);
+        let mut sets = Vec::new();
+        if default_exclusions {
+            sets.push(default_rules());
+        }
+        if !folder.exclude.is_empty() {
+            sets.push(Arc::new(parse_rules(
+                root,
+                folder.exclude.iter().map(String::as_str),
+            )));
+        }
+        Self { sets }
+    }
+
+    /// Rules in effect inside `dir`: the current ones plus its ignore file, if any
+    pub fn enter(&self, dir: &Path) -> Self {
+        let mut rules = self.clone();
+        if let Ok(content) = fs::read_to_string(dir.join(IGNORE_FILE_NAME)) {
+            rules.sets.push(Arc::new(parse_rules(dir, content.lines())));
+        }
+        rules
+    }
+
+    /// Whether a file or directory (a direct child of the last entered directory) is excluded
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let name = match path.file_name() {
+            Some(name) => name,
+            None => return false,
+        };
+        let mut ignored = false;
+
+        for set in &self.sets {
+            let relative = path.strip_prefix(&set.base).unwrap_or(path);
+            for rule in &set.rules {
+                if rule.dir_only && !is_dir {
+                    continue;
+                }
+                let matched = if rule.anchored {
+                    rule.matcher.is_match(relative)
+                } else {
+                    rule.matcher.is_match(name)
+                };
+                if matched {
+                    ignored = !rule.negate;
+                }
+            }
+        }
+
+        ignored
+    }
+}
+
+/// Whether a path inside one of the configured folders is excluded, either
+/// itself or through one of its parent directories (used by the watcher)
+pub fn is_path_ignored(config: &MediaConfig, path: &Path, is_dir: bool) -> bool {
+    // The innermost configured folder containing the path owns its rules
+    let folder = match config.folder_for(path) {
+        Some(folder) => folder,
+        None => return false,
+    };
+
+    let root = Path::new(&folder.path);
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) => return false,
+    };
+    let components: Vec<Component> = relative.components().collect();
+
+    let mut rules = IgnoreRules::for_folder(folder, config.default_exclusions).enter(root);
+    let mut current = root.to_path_buf();
+    for (i, component) in components.iter().enumerate() {
+        current.push(component);
+        let last = i + 1 == components.len();
+        if rules.is_ignored(&current, !last || is_dir) {
+            return true;
+        }
+        if !last {
+            rules = rules.enter(&current);
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::testing::TempDir;
+    use serde_json::json;
+
+    fn folder(path: &Path, exclude: &[&str]) -> MediaFolder {
+        serde_json::from_value(json!({"path": path, "exclude": exclude})).unwrap()
+    }
+
+    fn config(folder: MediaFolder, default_exclusions: bool) -> MediaConfig {
+        MediaConfig {
+            folders: vec![folder],
+            default_exclusions,
+        }
+    }
+
+    #[test]
+    fn rules_are_parsed_like_gitignore_lines() {
+        for line in ["", "   ", "# comment", "!", "/", "!/"] {
+            assert!(parse_rule(line).unwrap().is_none(), "{:?}", line);
+        }
+        assert!(parse_rule("[").is_err());
+
+        let rule = parse_rule(" !*.NFO ").unwrap().unwrap();
+        assert!(rule.negate && !rule.dir_only && !rule.anchored);
+        assert!(rule.matcher.is_match("movie.nfo"));
+
+        let rule = parse_rule("Extras/").unwrap().unwrap();
+        assert!(!rule.negate && rule.dir_only && !rule.anchored);
+
+        let rule = parse_rule("/Extras").unwrap().unwrap();
+        assert!(rule.anchored && rule.matcher.is_match("extras"));
+
+        let rule = parse_rule("Season */*.txt").unwrap().unwrap();
+        assert!(rule.anchored);
+        assert!(rule.matcher.is_match("Season 1/notes.txt"));
+        assert!(!rule.matcher.is_match("Show/Season 1/notes.txt"));
+    }
+
+    #[test]
+    fn name_patterns_match_at_any_depth_and_anchored_ones_from_the_base() {
+        let root = Path::new("/lib");
+        let rules =
+            IgnoreRules::for_folder(&folder(root, &["*.nfo", "/Extras", "Show/Bonus/"]), false);
+        let ignored = |path: &str, is_dir: bool| rules.is_ignored(&root.join(path), is_dir);
+
+        assert!(ignored("Movie.nfo", false));
+        assert!(ignored("Show/Season 1/Episode.NFO", false));
+        assert!(!ignored("Movie.mkv", false));
+
+        assert!(ignored("Extras", true));
+        assert!(!ignored("Show/Extras", true));
+
+        // Directory-only rules skip files of the same name
+        assert!(ignored("Show/Bonus", true));
+        assert!(!ignored("Show/Bonus", false));
+    }
+
+    #[test]
+    fn the_last_matching_rule_wins() {
+        let root = Path::new("/lib");
+        let rules =
+            IgnoreRules::for_folder(&folder(root, &["*.mkv", "!Keep*", "Keep.Not.mkv"]), false);
+        assert!(rules.is_ignored(&root.join("Movie.mkv"), false));
+        assert!(!rules.is_ignored(&root.join("Keep.mkv"), false));
+        assert!(rules.is_ignored(&root.join("Keep.Not.mkv"), false));
+    }
+
+    #[test]
+    fn defaults_skip_hidden_files_samples_and_nas_folders() {
+        let root = Path::new("/lib");
+        let rules = IgnoreRules::for_folder(&folder(root, &[]), true);
+        let ignored = |path: &str, is_dir: bool| rules.is_ignored(&root.join(path), is_dir);
+
+        assert!(ignored(".Trash-1000", true));
+        assert!(ignored("._Movie.mkv", false));
+        assert!(ignored("Movie/Sample", true));
+        assert!(ignored("Movie/Movie-sample.mkv", false));
+        assert!(ignored("@eaDir", true));
+        assert!(ignored("$RECYCLE.BIN", true));
+        assert!(!ignored("Movie/Movie.mkv", false));
+        // "sample/" only excludes folders
+        assert!(!ignored("Sample", false));
+
+        let rules = IgnoreRules::for_folder(&folder(root, &[]), false);
+        assert!(!rules.is_ignored(&root.join(".Trash-1000"), true));
+
+        // Folder rules can re-include what the defaults exclude
+        let rules = IgnoreRules::for_folder(&folder(root, &["!.keep"]), true);
+        assert!(!rules.is_ignored(&root.join(".keep"), false));
+    }
+
+    #[test]
+    fn ignore_files_stack_from_the_root_down() {
+        let dir = TempDir::new();
+        dir.write(IGNORE_FILE_NAME, "*.txt\nOld/\n");
+        dir.write(
+            &format!("Show/{}", IGNORE_FILE_NAME),
+            "# Keep the notes of this show\n!notes.txt\n/Season 2\n",
+        );
+        let config = config(folder(dir.path(), &[]), true);
+        let ignored =
+            |path: &str, is_dir: bool| is_path_ignored(&config, &dir.path().join(path), is_dir);
+
+        assert!(ignored("readme.txt", false));
+        assert!(ignored("Show/readme.txt", false));
+        assert!(!ignored("Show/notes.txt", false));
+        assert!(ignored("notes.txt", false));
+
+        // Anchored rules of an ignore file are relative to its directory
+        assert!(ignored("Show/Season 2", true));
+        assert!(ignored("Show/Season 2/Episode.mkv", false));
+        assert!(!ignored("Season 2/Episode.mkv", false));
+
+        // Excluded parents exclude everything below them
+        assert!(ignored("Old/Movie.mkv", false));
+        assert!(ignored("Show/.hidden/Episode.mkv", false));
+        assert!(!ignored("Show/Season 1/Episode.mkv", false));
+
+        // Paths outside the configured folders are never excluded
+        assert!(!is_path_ignored(
+            &config,
+            Path::new("/elsewhere/readme.txt"),
+            false
+        ));
+    }
+}
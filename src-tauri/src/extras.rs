@@ -0,0 +1,397 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::media::{MediaConfig, MediaWithMetadata};
+
+/// Kind of bonus material, from its folder or filename suffix
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtraKind {
+    Trailer,
+    Featurette,
+    BehindTheScenes,
+    DeletedScene,
+    Interview,
+    Scene,
+    Short,
+    /// Anything found in an `Extras` folder
+    Extra,
+}
+
+/// Folder names holding extras, lowercased without spaces, dashes or underscores
+const EXTRA_FOLDERS: &[(&str, ExtraKind)] = &[
+    ("extras", ExtraKind::Extra),
+    ("extra", ExtraKind::Extra),
+    ("trailers", ExtraKind::Trailer),
+    ("featurettes", ExtraKind::Featurette),
+    ("behindthescenes", ExtraKind::BehindTheScenes),
+    ("deletedscenes", ExtraKind::DeletedScene),
+    ("interviews", ExtraKind::Interview),
+    ("scenes", ExtraKind::Scene),
+    ("shorts", ExtraKind::Short),
+];
+
+/// Filename suffixes marking an extra next to its title ("Inception (2010)-trailer.mkv")
+const EXTRA_SUFFIXES: &[(&str, ExtraKind)] = &[
+    ("-trailer", ExtraKind::Trailer),
+    ("-featurette", ExtraKind::Featurette),
+    ("-behindthescenes", ExtraKind::BehindTheScenes),
+    ("-deleted", ExtraKind::DeletedScene),
+    ("-interview", ExtraKind::Interview),
+    ("-scene", ExtraKind::Scene),
+    ("-short", ExtraKind::Short),
+];
+
+/// An extra as listed on its parent title
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaExtra {
+    pub id: String,
+    pub path: String,
+    pub filename: String,
+    pub size_bytes: u64,
+    pub kind: ExtraKind,
+    /// Name of the extra, without its kind suffix
+    pub title: String,
+}
+
+fn folder_kind(name: &str) -> Option<ExtraKind> {
+    let normalized: String = name
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_' | '.'))
+        .collect::<String>()
+        .to_lowercase();
+    EXTRA_FOLDERS
+        .iter()
+        .find(|(folder, _)| *folder == normalized)
+        .map(|(_, kind)| *kind)
+}
+
+fn suffix_kind(stem: &str) -> Option<(ExtraKind, usize)> {
+    let lower = stem.to_lowercase();
+    if lower == "trailer" {
+        return Some((ExtraKind::Trailer, 0));
+    }
+    EXTRA_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| lower.ends_with(suffix))
+        .map(|(suffix, kind)| (*kind, stem.len() - suffix.len()))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Kind of extra a video file is: a filename suffix wins over its folder
+/// (`Extras/Movie-trailer.mkv` is a trailer)
+pub fn extra_kind(path: &Path) -> Option<ExtraKind> {
+    if let Some((kind, _)) = suffix_kind(&file_stem(path)) {
+        return Some(kind);
+    }
+    let parent = path.parent()?.file_name()?.to_str()?;
+    folder_kind(parent)
+}
+
+/// Display name of an extra: its filename without the kind suffix
+pub fn extra_title(path: &Path) -> String {
+    let stem = file_stem(path);
+    let name = match suffix_kind(&stem) {
+        Some((_, 0)) => stem.as_str(),
+        Some((_, end)) => &stem[..end],
+        None => stem.as_str(),
+    };
+    name.replace(['.', '_'], " ").trim().to_string()
+}
+
+/// Directory whose titles an extra belongs to: the one holding it, or the one
+/// above its extras folders (`Movie/Extras/Trailers/a.mkv` belongs to `Movie`)
+fn owner_dir(path: &Path) -> Option<&Path> {
+    let mut dir = path.parent()?;
+    while dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .and_then(folder_kind)
+        .is_some()
+    {
+        dir = dir.parent()?;
+    }
+    Some(dir)
+}
+
+/// Directories a title lives in: the folder of each of its files, and for a
+/// Blu-ray/DVD folder the disc folder itself (extras are often ripped inside it)
+fn title_dirs(item: &MediaWithMetadata) -> Vec<PathBuf> {
+    let is_disc_folder = item
+        .entry
+        .disc
+        .as_ref()
+        .is_some_and(|d| d.format != crate::disc::DiscFormat::Iso);
+    let paths = std::iter::once(item.entry.path.as_str())
+        .chain(item.versions.iter().map(|v| v.path.as_str()));
+
+    let mut dirs = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if is_disc_folder {
+            dirs.push(path.to_path_buf());
+        }
+        if let Some(parent) = path.parent() {
+            dirs.push(parent.to_path_buf());
+        }
+    }
+    dirs
+}
+
+/// What makes two titles the same show or movie (episodes of a series share it)
+fn show_key(item: &MediaWithMetadata) -> String {
+    let meta = item.metadata.as_ref();
+    let tmdb_id = meta
+        .and_then(|m| m.get("tmdb_id"))
+        .and_then(|id| id.as_u64());
+    let media_type = meta
+        .and_then(|m| m.get("media_type"))
+        .and_then(|t| t.as_str())
+        .unwrap_or("unknown");
+    match tmdb_id {
+        Some(id) => format!("{}:{}", media_type, id),
+        None => match media_type {
+            // Unmatched episodes only share their parsed series title
+            "tv" => meta
+                .and_then(|m| m.get("title"))
+                .and_then(|t| t.as_str())
+                .map(|t| format!("tv:{}", t.to_lowercase()))
+                .unwrap_or_else(|| item.entry.id.clone()),
+            _ => item.entry.id.clone(),
+        },
+    }
+}
+
+/// Title an extra belongs to, looking in its owner directory first and then
+/// up to the library folder root. Titles found at a level must all be the same
+/// show or movie, unless the extra is named after one of them.
+fn find_parent(
+    extra: &Path,
+    titles: &[(usize, Vec<PathBuf>)],
+    items: &[MediaWithMetadata],
+    root: Option<&Path>,
+) -> Option<usize> {
+    let stem = file_stem(extra);
+    let named_after = match suffix_kind(&stem) {
+        Some((_, end)) if end > 0 => Some(stem[..end].to_lowercase()),
+        _ => None,
+    };
+
+    let mut dir = owner_dir(extra)?;
+    loop {
+        let candidates: Vec<usize> = titles
+            .iter()
+            .filter(|(_, dirs)| dirs.iter().any(|d| d.starts_with(dir)))
+            .map(|(i, _)| *i)
+            .collect();
+
+        if !candidates.is_empty() {
+            if let Some(ref name) = named_after {
+                let named = candidates.iter().copied().find(|&i| {
+                    file_stem(Path::new(&items[i].entry.path)).to_lowercase() == *name
+                        || items[i].entry.filename.to_lowercase() == *name
+                });
+                if named.is_some() {
+                    return named;
+                }
+            }
+            let key = show_key(&items[candidates[0]]);
+            if candidates.iter().all(|&i| show_key(&items[i]) == key) {
+                return candidates
+                    .into_iter()
+                    .min_by(|&a, &b| items[a].entry.path.cmp(&items[b].entry.path));
+            }
+            return None;
+        }
+
+        if root.is_none_or(|root| dir == root) {
+            return None;
+        }
+        dir = dir.parent()?;
+    }
+}
+
+/// Move extras from the library list to the `extras` of the title they belong
+/// to. Extras with no clear parent stay in the list on their own.
+pub fn attach_extras(
+    items: Vec<MediaWithMetadata>,
+    config: &MediaConfig,
+) -> Vec<MediaWithMetadata> {
+    if items.iter().all(|item| item.entry.extra.is_none()) {
+        return items;
+    }
+
+    let titles: Vec<(usize, Vec<PathBuf>)> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.entry.extra.is_none())
+        .map(|(i, item)| (i, title_dirs(item)))
+        .collect();
+
+    let mut parents: Vec<Option<usize>> = vec![None; items.len()];
+    for (i, item) in items.iter().enumerate() {
+        if item.entry.extra.is_none() {
+            continue;
+        }
+        let path = Path::new(&item.entry.path);
+        let root = config.folder_for(path).map(|f| Path::new(&f.path));
+        parents[i] = find_parent(path, &titles, &items, root);
+        if parents[i].is_none() {
+            log::debug!(
+                "[Media] No parent title found for extra {}",
+                item.entry.path
+            );
+        }
+    }
+
+    let mut extras: Vec<Vec<MediaExtra>> = vec![Vec::new(); items.len()];
+    for (i, item) in items.iter().enumerate() {
+        if let (Some(parent), Some(kind)) = (parents[i], item.entry.extra) {
+            extras[parent].push(MediaExtra {
+                id: item.entry.id.clone(),
+                path: item.entry.path.clone(),
+                filename: item.entry.filename.clone(),
+                size_bytes: item.entry.size_bytes,
+                kind,
+                title: extra_title(Path::new(&item.entry.path)),
+            });
+        }
+    }
+
+    items
+        .into_iter()
+        .zip(parents.into_iter().zip(extras))
+        .filter_map(|(mut item, (parent, mut extras))| {
+            if parent.is_some() {
+                return None;
+            }
+            extras.sort_by(|a, b| a.path.cmp(&b.path));
+            item.extras = extras;
+            Some(item)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::MediaFolder;
+    use serde_json::json;
+
+    fn item(path: &str, metadata: Option<serde_json::Value>) -> MediaWithMetadata {
+        let mut item = MediaWithMetadata::test(path);
+        item.metadata = metadata;
+        item.entry.extra = extra_kind(Path::new(path));
+        item
+    }
+
+    fn movie(path: &str, tmdb_id: u64) -> MediaWithMetadata {
+        item(
+            path,
+            Some(json!({"media_type": "movie", "tmdb_id": tmdb_id})),
+        )
+    }
+
+    fn episode(path: &str) -> MediaWithMetadata {
+        item(path, Some(json!({"media_type": "tv", "tmdb_id": 1})))
+    }
+
+    fn attach(items: Vec<MediaWithMetadata>) -> Vec<(String, Vec<String>)> {
+        let config = MediaConfig {
+            folders: vec![MediaFolder::new("/lib".to_string())],
+            default_exclusions: true,
+        };
+        let mut result: Vec<(String, Vec<String>)> = attach_extras(items, &config)
+            .into_iter()
+            .map(|item| {
+                let extras = item.extras.into_iter().map(|e| e.path).collect();
+                (item.entry.path, extras)
+            })
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn extras_are_recognized_by_suffix_then_folder() {
+        let kind = |path: &str| extra_kind(Path::new(path));
+        assert_eq!(kind("/lib/Heat/Heat-trailer.mkv"), Some(ExtraKind::Trailer));
+        assert_eq!(
+            kind("/lib/Heat/Extras/Heat-interview.mkv"),
+            Some(ExtraKind::Interview)
+        );
+        assert_eq!(
+            kind("/lib/Heat/Behind The Scenes/Shoot.mkv"),
+            Some(ExtraKind::BehindTheScenes)
+        );
+        assert_eq!(kind("/lib/Heat/Heat.mkv"), None);
+        assert_eq!(
+            extra_title(Path::new("/lib/Heat/Heat.1995-trailer.mkv")),
+            "Heat 1995"
+        );
+    }
+
+    #[test]
+    fn extras_attach_to_the_title_of_their_folder() {
+        let result = attach(vec![
+            movie("/lib/Heat (1995)/Heat.mkv", 949),
+            item("/lib/Heat (1995)/Extras/Trailers/Teaser.mkv", None),
+            item("/lib/Heat (1995)/Heat-featurette.mkv", None),
+        ]);
+        assert_eq!(
+            result,
+            vec![(
+                "/lib/Heat (1995)/Heat.mkv".to_string(),
+                vec![
+                    "/lib/Heat (1995)/Extras/Trailers/Teaser.mkv".to_string(),
+                    "/lib/Heat (1995)/Heat-featurette.mkv".to_string(),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn extras_of_a_shared_folder_need_a_name_to_find_their_parent() {
+        let result = attach(vec![
+            movie("/lib/Movies/Alien.mkv", 348),
+            movie("/lib/Movies/Brazil.mkv", 68),
+            item("/lib/Movies/Alien-trailer.mkv", None),
+            item("/lib/Movies/Trailers/Teaser.mkv", None),
+        ]);
+        assert_eq!(
+            result,
+            vec![
+                (
+                    "/lib/Movies/Alien.mkv".to_string(),
+                    vec!["/lib/Movies/Alien-trailer.mkv".to_string()]
+                ),
+                ("/lib/Movies/Brazil.mkv".to_string(), vec![]),
+                ("/lib/Movies/Trailers/Teaser.mkv".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn extras_of_a_series_attach_to_its_first_episode() {
+        let result = attach(vec![
+            episode("/lib/Show/Season 2/S02E01.mkv"),
+            episode("/lib/Show/Season 1/S01E01.mkv"),
+            item("/lib/Show/Extras/Bloopers.mkv", None),
+        ]);
+        assert_eq!(
+            result,
+            vec![
+                (
+                    "/lib/Show/Season 1/S01E01.mkv".to_string(),
+                    vec!["/lib/Show/Extras/Bloopers.mkv".to_string()]
+                ),
+                ("/lib/Show/Season 2/S02E01.mkv".to_string(), vec![]),
+            ]
+        );
+    }
+}
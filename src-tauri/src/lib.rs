@@ -1,8 +1,17 @@
+mod db;
+mod disc;
+mod exclusions;
+mod extras;
+mod library;
 mod media;
 mod metadata;
 mod peer;
 mod player;
+mod session;
+mod subtitles;
 mod user;
+mod vault;
+mod versions;
 mod watcher;
 
 use std::sync::{Arc, Mutex};
@@ -37,14 +46,30 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .manage(Arc::new(Mutex::new(watcher::WatcherState::new())))
         .manage(Arc::new(Mutex::new(metadata::MetadataRetryState::new())))
+        .manage(Arc::new(Mutex::new(vault::VaultState::new())))
+        .manage(Arc::new(Mutex::new(db::DatabaseState::new())))
+        .manage(Arc::new(Mutex::new(library::LibraryIndexState::new())))
+        .manage(Arc::new(Mutex::new(session::SessionState::new())))
         .manage(Arc::new(tokio::sync::Mutex::new(peer::PeerState::new())))
         .invoke_handler(tauri::generate_handler![
             greet,
             user::check_user_exists,
+            user::list_users,
             user::create_user,
             user::verify_pin,
+            user::logout,
+            user::get_session,
             user::update_username,
             user::update_pin,
+            user::reset_pin_with_recovery,
+            user::regenerate_recovery_code,
+            user::has_recovery_code,
+            user::delete_user,
+            user::export_account,
+            user::inspect_account_bundle,
+            user::import_account,
+            user::get_vault_mode,
+            user::set_vault_mode,
             user::save_avatar,
             user::remove_avatar,
             user::get_avatar,
@@ -53,6 +78,9 @@ pub fn run() {
             media::save_media_config,
             media::add_media_folder,
             media::remove_media_folder,
+            media::set_folder_exclusions,
+            media::set_folder_options,
+            media::get_offline_folders,
             media::scan_media_folders,
             media::get_media_library,
             media::pick_folder,
@@ -61,8 +89,11 @@ pub fn run() {
             media::start_media_watcher,
             media::stop_media_watcher,
             media::fetch_all_metadata,
+            media::collect_metadata_garbage,
             media::get_media_metadata,
             media::get_library_with_metadata,
+            library::query_library,
+            library::get_recently_added,
             media::cleanup_old_logs,
             peer::peer_start,
             peer::peer_stop,
@@ -71,8 +102,10 @@ pub fn run() {
             peer::peer_disconnect,
             peer::peer_list,
             player::player_open_vlc,
+            versions::set_default_version,
         ])
-        .setup(|_app| {
+        .setup(|app| {
+            session::spawn_idle_monitor(app.handle().clone());
             log::info!("[App] Popcorn Hero started");
             Ok(())
         })
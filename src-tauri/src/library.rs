@@ -0,0 +1,738 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+use crate::media::MediaWithMetadata;
+
+/// Page size when the query does not set one
+const DEFAULT_LIMIT: usize = 50;
+/// Largest page a query can ask for
+const MAX_LIMIT: usize = 500;
+
+/// Which titles a query returns. Empty lists and unset bounds match everything;
+/// a list matches any of its values.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LibraryFilter {
+    /// "movie", "tv", "home_video", "extra" or "unknown" (no metadata)
+    pub media_types: Vec<String>,
+    pub genres: Vec<String>,
+    pub year_min: Option<u32>,
+    pub year_max: Option<u32>,
+    pub rating_min: Option<f64>,
+    pub rating_max: Option<f64>,
+    /// File extensions ("mkv", "iso"...)
+    pub containers: Vec<String>,
+    /// Metadata providers ("tmdb", "local"...)
+    pub providers: Vec<String>,
+    /// Only titles whose drive is connected (true) or not (false)
+    pub available: Option<bool>,
+    /// Full-text search of the metadata (titles, people, studios, overviews),
+    /// where every word must start a word, case and accents aside; titles whose
+    /// filename holds every word match too
+    pub text: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LibrarySort {
+    #[default]
+    Title,
+    Year,
+    Rating,
+    DateAdded,
+    Size,
+    /// Best matches of the `text` filter first
+    Relevance,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LibraryQuery {
+    #[serde(default)]
+    pub filter: LibraryFilter,
+    #[serde(default)]
+    pub sort: LibrarySort,
+    #[serde(default)]
+    pub descending: bool,
+    /// `next_cursor` of the previous page, None for the first one
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// One page of a query
+#[derive(Serialize, Clone, Debug)]
+pub struct LibraryPage {
+    pub items: Vec<MediaWithMetadata>,
+    /// Titles matching the filter, over all pages
+    pub total: usize,
+    /// Cursor of the next page, None on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Value a title is sorted on. Titles without one (no year, no rating) come last
+/// in both directions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd)]
+#[serde(untagged)]
+enum SortValue {
+    Number(f64),
+    Text(String),
+}
+
+/// Position after the last title of a page. Titles added or removed between
+/// two pages shift nothing: the next page starts after this sort value and path.
+#[derive(Serialize, Deserialize, Debug)]
+struct Cursor {
+    sort: LibrarySort,
+    descending: bool,
+    /// `filter_digest` of the query's filter
+    filter: String,
+    value: Option<SortValue>,
+    path: String,
+}
+
+/// A title of the library view with the fields queries filter and sort on
+struct IndexedTitle {
+    item: MediaWithMetadata,
+    media_type: String,
+    title: String,
+    year: Option<u32>,
+    rating: Option<f64>,
+    /// Lowercased
+    genres: Vec<String>,
+    provider: Option<String>,
+    /// Lowercased, for text searches of titles without metadata
+    filename: String,
+}
+
+impl IndexedTitle {
+    fn new(item: MediaWithMetadata) -> Self {
+        let meta = item.metadata.as_ref();
+        let text = |key: &str| meta.and_then(|m| m.get(key)).and_then(|v| v.as_str());
+
+        let title = text("title").map(str::to_string).unwrap_or_else(|| {
+            // Filename without its extension, as the cards show it
+            Path::new(&item.entry.filename)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+        Self {
+            media_type: text("media_type").unwrap_or("unknown").to_string(),
+            title: title.to_lowercase(),
+            year: meta
+                .and_then(|m| m.get("year"))
+                .and_then(|v| v.as_u64())
+                .map(|y| y as u32),
+            rating: meta.and_then(|m| m.get("rating")).and_then(|v| v.as_f64()),
+            genres: meta
+                .and_then(|m| m.get("genres"))
+                .and_then(|v| v.as_array())
+                .map(|genres| {
+                    genres
+                        .iter()
+                        .filter_map(|g| g.as_str())
+                        .map(|g| g.to_lowercase())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            provider: text("provider").map(str::to_string),
+            filename: item.entry.filename.to_lowercase(),
+            item,
+        }
+    }
+
+    fn sort_value(&self, sort: LibrarySort, relevance: Option<f64>) -> Option<SortValue> {
+        match sort {
+            LibrarySort::Title => Some(SortValue::Text(self.title.clone())),
+            LibrarySort::Year => self.year.map(|y| SortValue::Number(y as f64)),
+            LibrarySort::Rating => self.rating.map(SortValue::Number),
+            LibrarySort::DateAdded => Some(SortValue::Number(self.item.entry.added_at as f64)),
+            LibrarySort::Size => Some(SortValue::Number(self.item.entry.size_bytes as f64)),
+            LibrarySort::Relevance => relevance.map(SortValue::Number),
+        }
+    }
+
+    /// Key shared by the episodes of a series: its TMDB ID, else its title
+    fn series_key(&self) -> String {
+        match self
+            .item
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get("tmdb_id"))
+            .and_then(|v| v.as_u64())
+        {
+            Some(tmdb_id) => format!("tmdb:{}", tmdb_id),
+            None => self.title.clone(),
+        }
+    }
+
+    /// Best full-text score among the versions of the title, None without a hit
+    fn relevance(&self, hits: &HashMap<String, f64>) -> Option<f64> {
+        std::iter::once(self.item.entry.id.as_str())
+            .chain(self.item.versions.iter().map(|v| v.id.as_str()))
+            .filter_map(|id| hits.get(id).copied())
+            .reduce(f64::min)
+    }
+
+    /// Whether the title passes every filter but the text
+    fn matches(&self, filter: &LibraryFilter) -> bool {
+        let any_of = |values: &[String], value: &str| {
+            values.is_empty() || values.iter().any(|v| v.eq_ignore_ascii_case(value))
+        };
+
+        any_of(&filter.media_types, &self.media_type)
+            && (filter.genres.is_empty()
+                || filter
+                    .genres
+                    .iter()
+                    .any(|g| self.genres.contains(&g.to_lowercase())))
+            && in_range(self.year, filter.year_min, filter.year_max)
+            && in_range(self.rating, filter.rating_min, filter.rating_max)
+            && any_of(&filter.containers, &self.item.entry.extension)
+            && (filter.providers.is_empty()
+                || self
+                    .provider
+                    .as_deref()
+                    .is_some_and(|p| any_of(&filter.providers, p)))
+            && filter
+                .available
+                .is_none_or(|available| self.item.entry.available == available)
+    }
+}
+
+/// Whether `value` lies within the inclusive bounds; a missing value only
+/// passes when there are none
+fn in_range<T: PartialOrd>(value: Option<T>, min: Option<T>, max: Option<T>) -> bool {
+    match value {
+        Some(value) => min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max),
+        None => min.is_none() && max.is_none(),
+    }
+}
+
+/// Order of two titles by sort value, then path so that every title has its place
+fn compare(
+    a: (&Option<SortValue>, &str),
+    b: (&Option<SortValue>, &str),
+    descending: bool,
+) -> Ordering {
+    let by_value = match (a.0, b.0) {
+        (Some(x), Some(y)) => {
+            let order = x.partial_cmp(y).unwrap_or(Ordering::Equal);
+            if descending {
+                order.reverse()
+            } else {
+                order
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+    by_value.then_with(|| a.1.cmp(b.1))
+}
+
+fn encode_cursor(cursor: &Cursor) -> Result<String, String> {
+    let json = serde_json::to_vec(cursor).map_err(|e| e.to_string())?;
+    Ok(BASE64.encode(json))
+}
+
+/// Short digest of a filter, so that a cursor is only followed with the filter
+/// of its page
+fn filter_digest(filter: &LibraryFilter) -> String {
+    let json = serde_json::to_vec(filter).unwrap_or_default();
+    Sha256::digest(json)[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode_cursor(raw: &str) -> Result<Cursor, String> {
+    BASE64
+        .decode(raw)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| "Invalid library cursor".to_string())
+}
+
+/// The library view of a user, kept until the database or the settings it was
+/// built from change
+struct LibraryIndex {
+    user_id: String,
+    revision: u64,
+    /// Settings the view depends on (folders, default version), serialized
+    settings: String,
+    titles: Vec<IndexedTitle>,
+}
+
+pub struct LibraryIndexState {
+    index: Option<Arc<LibraryIndex>>,
+}
+
+impl LibraryIndexState {
+    pub fn new() -> Self {
+        Self { index: None }
+    }
+}
+
+/// Drop the user's index (database closed)
+pub fn forget(app: &AppHandle, user_id: &str) {
+    let state = app.state::<Arc<Mutex<LibraryIndexState>>>();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    if state
+        .index
+        .as_ref()
+        .is_some_and(|index| index.user_id == user_id)
+    {
+        state.index = None;
+    }
+}
+
+/// The user's index, rebuilt if the library changed since it was built
+fn index(app: &AppHandle, user_id: &str) -> Result<Arc<LibraryIndex>, String> {
+    let settings = crate::media::load_settings(app, user_id).unwrap_or_default();
+    let settings = serde_json::to_string(&(&settings.media, settings.default_version))
+        .map_err(|e| e.to_string())?;
+    // Read before building: a write during the build makes the next query rebuild
+    let revision = crate::db::revision();
+
+    let state = app.state::<Arc<Mutex<LibraryIndexState>>>();
+    if let Some(index) = state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .index
+        .as_ref()
+    {
+        if index.user_id == user_id && index.revision == revision && index.settings == settings {
+            return Ok(index.clone());
+        }
+    }
+
+    let titles: Vec<IndexedTitle> = crate::media::library_with_metadata(app, user_id)?
+        .into_iter()
+        .map(IndexedTitle::new)
+        .collect();
+    log::info!(
+        "[Media] Indexed {} titles for user {}",
+        titles.len(),
+        user_id
+    );
+
+    let index = Arc::new(LibraryIndex {
+        user_id: user_id.to_string(),
+        revision,
+        settings,
+        titles,
+    });
+    state.lock().unwrap_or_else(|e| e.into_inner()).index = Some(index.clone());
+    Ok(index)
+}
+
+/// Filter, sort and page the library view without sending all of it
+#[tauri::command]
+pub fn query_library(
+    app: AppHandle,
+    user_id: String,
+    query: LibraryQuery,
+) -> Result<LibraryPage, String> {
+    crate::session::require(&app, &user_id)?;
+    let index = index(&app, &user_id)?;
+    let words = search_words(&query.filter);
+    let hits = if words.is_empty() {
+        HashMap::new()
+    } else {
+        crate::db::search_metadata(&app, &user_id, &words.join(" "))?
+    };
+    page(&index.titles, &query, &hits)
+}
+
+/// Lowercased words of the text filter
+fn search_words(filter: &LibraryFilter) -> Vec<String> {
+    filter
+        .text
+        .as_deref()
+        .unwrap_or_default()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// The page of `titles` a query asks for, `hits` being the full-text scores of
+/// its text filter
+fn page(
+    titles: &[IndexedTitle],
+    query: &LibraryQuery,
+    hits: &HashMap<String, f64>,
+) -> Result<LibraryPage, String> {
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => {
+            let cursor = decode_cursor(raw)?;
+            if cursor.sort != query.sort
+                || cursor.descending != query.descending
+                || cursor.filter != filter_digest(&query.filter)
+            {
+                return Err("The cursor belongs to a query with another sort or filter".to_string());
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let words = search_words(&query.filter);
+
+    let mut matching: Vec<(Option<SortValue>, &IndexedTitle)> = titles
+        .iter()
+        .filter(|title| title.matches(&query.filter))
+        .filter_map(|title| {
+            let relevance = title.relevance(hits);
+            if relevance.is_none() && !words.iter().all(|w| title.filename.contains(w.as_str())) {
+                return None;
+            }
+            Some((title.sort_value(query.sort, relevance), title))
+        })
+        .collect();
+    matching.sort_by(|a, b| {
+        compare(
+            (&a.0, &a.1.item.entry.path),
+            (&b.0, &b.1.item.entry.path),
+            query.descending,
+        )
+    });
+
+    let start = match &cursor {
+        Some(cursor) => matching.partition_point(|(value, title)| {
+            compare(
+                (value, &title.item.entry.path),
+                (&cursor.value, &cursor.path),
+                query.descending,
+            ) != Ordering::Greater
+        }),
+        None => 0,
+    };
+    let end = (start + limit).min(matching.len());
+
+    let next_cursor = match matching[start..end].last() {
+        Some((value, title)) if end < matching.len() => Some(encode_cursor(&Cursor {
+            sort: query.sort,
+            descending: query.descending,
+            filter: filter_digest(&query.filter),
+            value: value.clone(),
+            path: title.item.entry.path.clone(),
+        })?),
+        _ => None,
+    };
+
+    Ok(LibraryPage {
+        items: matching[start..end]
+            .iter()
+            .map(|(_, title)| title.item.clone())
+            .collect(),
+        total: matching.len(),
+        next_cursor,
+    })
+}
+
+/// An entry of the recently added list
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecentTitle {
+    /// A movie, home video or unidentified file
+    Media { item: Box<MediaWithMetadata> },
+    /// Recently added episodes of a series, newest first
+    Series {
+        title: String,
+        /// When its newest episode was added
+        added_at: i64,
+        /// Poster of the newest episode
+        poster_path: Option<String>,
+        episodes: Vec<MediaWithMetadata>,
+    },
+}
+
+/// The newest titles of the library, newest first. The episodes of a series
+/// count as one title, placed where its newest episode was added.
+#[tauri::command]
+pub fn get_recently_added(
+    app: AppHandle,
+    user_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<RecentTitle>, String> {
+    crate::session::require(&app, &user_id)?;
+    let index = index(&app, &user_id)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    Ok(recent_titles(&index.titles, limit))
+}
+
+/// The `limit` newest of `titles`, see `get_recently_added`
+fn recent_titles(titles: &[IndexedTitle], limit: usize) -> Vec<RecentTitle> {
+    // Extras are listed on their parent title
+    let mut newest: Vec<&IndexedTitle> = titles
+        .iter()
+        .filter(|title| title.media_type != "extra")
+        .collect();
+    newest.sort_by(|a, b| {
+        b.item
+            .entry
+            .added_at
+            .cmp(&a.item.entry.added_at)
+            .then_with(|| a.item.entry.path.cmp(&b.item.entry.path))
+    });
+
+    let mut recent: Vec<RecentTitle> = Vec::new();
+    let mut series: HashMap<String, usize> = HashMap::new();
+    for title in newest {
+        let key = (title.media_type == "tv").then(|| title.series_key());
+        if let Some(position) = key.as_ref().and_then(|key| series.get(key)) {
+            if let RecentTitle::Series { episodes, .. } = &mut recent[*position] {
+                episodes.push(title.item.clone());
+            }
+            continue;
+        }
+        // Once the list is full, older episodes still join their series
+        if recent.len() == limit {
+            continue;
+        }
+
+        match key {
+            Some(key) => {
+                series.insert(key, recent.len());
+                recent.push(RecentTitle::Series {
+                    title: title
+                        .item
+                        .metadata
+                        .as_ref()
+                        .and_then(|m| m.get("title"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(&title.item.entry.filename)
+                        .to_string(),
+                    added_at: title.item.entry.added_at,
+                    poster_path: title.item.poster_path.clone(),
+                    episodes: vec![title.item.clone()],
+                });
+            }
+            None => recent.push(RecentTitle::Media {
+                item: Box::new(title.item.clone()),
+            }),
+        }
+    }
+    recent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A title at `path`, with metadata when `title` is set
+    fn title(path: &str, title: Option<&str>, year: Option<u32>, size: u64) -> IndexedTitle {
+        let item = MediaWithMetadata::test(path).with_size(size);
+        IndexedTitle::new(
+            match title {
+                Some(title) => item.with_metadata(json!({
+                    "title": title,
+                    "year": year,
+                    "media_type": "movie",
+                    "genres": ["Drama"],
+                    "provider": "tmdb",
+                })),
+                None => item,
+            },
+        )
+    }
+
+    fn query(sort: LibrarySort, descending: bool, limit: usize) -> LibraryQuery {
+        LibraryQuery {
+            filter: LibraryFilter::default(),
+            sort,
+            descending,
+            cursor: None,
+            limit: Some(limit),
+        }
+    }
+
+    fn paths(page: &LibraryPage) -> Vec<&str> {
+        page.items.iter().map(|i| i.entry.path.as_str()).collect()
+    }
+
+    /// Paths of every page, following the cursors
+    fn all_pages(titles: &[IndexedTitle], mut query: LibraryQuery) -> Vec<String> {
+        let mut seen = Vec::new();
+        loop {
+            let page = page(titles, &query, &HashMap::new()).unwrap();
+            seen.extend(paths(&page).into_iter().map(str::to_string));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return seen,
+            }
+        }
+    }
+
+    fn library() -> Vec<IndexedTitle> {
+        vec![
+            title("/m/c.mkv", Some("Heat"), Some(1995), 30),
+            title("/m/a.mkv", Some("Alien"), Some(1979), 10),
+            title("/m/e.mkv", None, None, 50),
+            title("/m/b.mkv", Some("Brazil"), Some(1985), 20),
+            title("/m/d.mkv", Some("Heat"), None, 40),
+        ]
+    }
+
+    #[test]
+    fn pages_list_every_title_once_in_order() {
+        let titles = library();
+        let first = page(
+            &titles,
+            &query(LibrarySort::Title, false, 2),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(first.total, 5);
+        assert_eq!(paths(&first), vec!["/m/a.mkv", "/m/b.mkv"]);
+        assert!(first.next_cursor.is_some());
+
+        // Equal titles are ordered by path, also across a page boundary
+        assert_eq!(
+            all_pages(&titles, query(LibrarySort::Title, false, 2)),
+            vec!["/m/a.mkv", "/m/b.mkv", "/m/e.mkv", "/m/c.mkv", "/m/d.mkv"]
+        );
+        assert_eq!(
+            all_pages(&titles, query(LibrarySort::Size, true, 3)),
+            vec!["/m/e.mkv", "/m/d.mkv", "/m/c.mkv", "/m/b.mkv", "/m/a.mkv"]
+        );
+    }
+
+    #[test]
+    fn titles_without_a_sort_value_come_last_in_both_directions() {
+        let titles = library();
+        assert_eq!(
+            all_pages(&titles, query(LibrarySort::Year, false, 2)),
+            vec!["/m/a.mkv", "/m/b.mkv", "/m/c.mkv", "/m/d.mkv", "/m/e.mkv"]
+        );
+        assert_eq!(
+            all_pages(&titles, query(LibrarySort::Year, true, 2)),
+            vec!["/m/c.mkv", "/m/b.mkv", "/m/a.mkv", "/m/d.mkv", "/m/e.mkv"]
+        );
+    }
+
+    #[test]
+    fn next_page_starts_after_the_cursor_when_the_library_changes() {
+        let mut titles = library();
+        let first = page(
+            &titles,
+            &query(LibrarySort::Size, false, 2),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(paths(&first), vec!["/m/a.mkv", "/m/b.mkv"]);
+
+        // A title of the first page leaves, a smaller one arrives
+        titles.retain(|t| t.item.entry.path != "/m/a.mkv");
+        titles.push(title("/m/f.mkv", Some("Fargo"), Some(1996), 5));
+        let mut next = query(LibrarySort::Size, false, 2);
+        next.cursor = first.next_cursor;
+        let second = page(&titles, &next, &HashMap::new()).unwrap();
+        assert_eq!(paths(&second), vec!["/m/c.mkv", "/m/d.mkv"]);
+    }
+
+    #[test]
+    fn cursors_round_trip_and_belong_to_their_query() {
+        let cursor = Cursor {
+            sort: LibrarySort::Rating,
+            descending: false,
+            filter: filter_digest(&LibraryFilter::default()),
+            value: Some(SortValue::Number(7.5)),
+            path: "/m/a b.mkv".to_string(),
+        };
+        let decoded = decode_cursor(&encode_cursor(&cursor).unwrap()).unwrap();
+        assert_eq!(decoded.sort, LibrarySort::Rating);
+        assert_eq!(decoded.value, Some(SortValue::Number(7.5)));
+        assert_eq!(decoded.path, "/m/a b.mkv");
+        assert!(decode_cursor("not a cursor").is_err());
+
+        let mut title_query = query(LibrarySort::Title, false, 2);
+        title_query.cursor = Some(encode_cursor(&cursor).unwrap());
+        assert!(page(&library(), &title_query, &HashMap::new()).is_err());
+
+        // A cursor only continues the order and filter of its own pages
+        let first = page(
+            &library(),
+            &query(LibrarySort::Title, false, 2),
+            &HashMap::new(),
+        )
+        .unwrap();
+        let mut reversed = query(LibrarySort::Title, true, 2);
+        reversed.cursor = first.next_cursor.clone();
+        assert!(page(&library(), &reversed, &HashMap::new()).is_err());
+        let mut filtered = query(LibrarySort::Title, false, 2);
+        filtered.filter.year_min = Some(1980);
+        filtered.cursor = first.next_cursor;
+        assert!(page(&library(), &filtered, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn filters_and_text_search_narrow_the_total() {
+        let titles = library();
+        let mut filtered = query(LibrarySort::Title, false, 10);
+        filtered.filter.year_min = Some(1980);
+        filtered.filter.genres = vec!["drama".to_string()];
+        let result = page(&titles, &filtered, &HashMap::new()).unwrap();
+        assert_eq!(paths(&result), vec!["/m/b.mkv", "/m/c.mkv"]);
+        assert_eq!(result.total, 2);
+
+        // Metadata hits sorted by relevance, plus filenames holding every word
+        let mut search = query(LibrarySort::Relevance, false, 10);
+        search.filter.text = Some("E".to_string());
+        let hits = HashMap::from([
+            ("id:/m/d.mkv".to_string(), -5.0),
+            ("id:/m/c.mkv".to_string(), -1.0),
+        ]);
+        let result = page(&titles, &search, &hits).unwrap();
+        assert_eq!(paths(&result), vec!["/m/d.mkv", "/m/c.mkv", "/m/e.mkv"]);
+    }
+
+    fn added(path: &str, media_type: &str, added_at: i64) -> IndexedTitle {
+        let mut item = MediaWithMetadata::test(path)
+            .with_metadata(json!({"title": path, "media_type": media_type, "tmdb_id": 1}));
+        item.entry.added_at = added_at;
+        IndexedTitle::new(item)
+    }
+
+    #[test]
+    fn recent_titles_group_every_episode_of_a_listed_series() {
+        let titles = vec![
+            added("/s/S01E01.mkv", "tv", 70),
+            added("/m/b.mkv", "movie", 80),
+            added("/s/S01E03.mkv", "tv", 100),
+            added("/m/a.mkv", "movie", 90),
+            added("/m/a-trailer.mkv", "extra", 95),
+            added("/m/c.mkv", "movie", 60),
+        ];
+        let recent: Vec<(String, Vec<String>)> = recent_titles(&titles, 2)
+            .into_iter()
+            .map(|title| match title {
+                RecentTitle::Media { item } => (item.entry.path, vec![]),
+                RecentTitle::Series { episodes, .. } => (
+                    "series".to_string(),
+                    episodes.into_iter().map(|e| e.entry.path).collect(),
+                ),
+            })
+            .collect();
+        assert_eq!(
+            recent,
+            vec![
+                (
+                    "series".to_string(),
+                    vec!["/s/S01E03.mkv".to_string(), "/s/S01E01.mkv".to_string()]
+                ),
+                ("/m/a.mkv".to_string(), vec![]),
+            ]
+        );
+    }
+}
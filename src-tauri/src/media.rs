@@ -1,35 +1,193 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tauri::{AppHandle, Manager};
-use uuid::Uuid;
+
+use crate::disc::{self, DiscFormat, DiscInfo};
+use crate::exclusions::IgnoreRules;
+use crate::extras::ExtraKind;
+use crate::subtitles::{Sidecars, SubtitleTrack};
+use crate::versions::VersionPreference;
 
 /// Supported media file extensions
 pub const VIDEO_EXTENSIONS: &[&str] = &[
     "mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "m4v", "mpg", "mpeg", "ts", "vob", "3gp",
     "ogv", "divx", "asf", "m2ts", "mts", "rmvb",
+    // Disc image, played as a whole (see `disc`)
+    "iso",
 ];
 
+/// Bytes hashed at the start and at the end of a file to fingerprint it
+const FINGERPRINT_CHUNK_BYTES: u64 = 64 * 1024;
+
 /// Configuration: list of directories to scan
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MediaConfig {
-    pub folders: Vec<String>,
+    pub folders: Vec<MediaFolder>,
+    /// Skip samples, NAS metadata folders and hidden files (see `exclusions`)
+    #[serde(default = "default_true")]
+    pub default_exclusions: bool,
 }
 
-/// A single media entry found during scanning
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            folders: vec![],
+            default_exclusions: true,
+        }
+    }
+}
+
+impl MediaConfig {
+    /// The innermost configured folder containing `path`
+    pub fn folder_for(&self, path: &Path) -> Option<&MediaFolder> {
+        self.folders
+            .iter()
+            .filter(|f| path.starts_with(&f.path))
+            .max_by_key(|f| f.path.len())
+    }
+}
+
+/// What a library folder contains, which drives filename parsing and metadata lookup
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LibraryType {
+    Movies,
+    Series,
+    /// Movies and episodes side by side, told apart by their filename
+    #[default]
+    Mixed,
+    /// Personal videos, never looked up on metadata providers
+    HomeVideos,
+}
+
+/// A library folder and its options
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(from = "StoredMediaFolder")]
+pub struct MediaFolder {
+    pub path: String,
+    /// Gitignore-like patterns, relative to the folder
+    pub exclude: Vec<String>,
+    pub library_type: LibraryType,
+    /// Language requested from metadata providers (e.g. "fr-FR"), provider default if unset
+    pub metadata_language: Option<String>,
+    /// Whether to look entries up on metadata providers (filename-only metadata otherwise)
+    pub fetch_metadata: bool,
+}
+
+impl MediaFolder {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            exclude: vec![],
+            library_type: LibraryType::default(),
+            metadata_language: None,
+            fetch_metadata: true,
+        }
+    }
+
+    /// Whether metadata providers may be queried for entries of this folder
+    pub fn uses_providers(&self) -> bool {
+        self.fetch_metadata && self.library_type != LibraryType::HomeVideos
+    }
+}
+
+/// Folders were stored as plain paths before they had options
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredMediaFolder {
+    Path(String),
+    Folder {
+        path: String,
+        #[serde(default)]
+        exclude: Vec<String>,
+        #[serde(default)]
+        library_type: LibraryType,
+        #[serde(default)]
+        metadata_language: Option<String>,
+        #[serde(default = "default_true")]
+        fetch_metadata: bool,
+    },
+}
+
+impl From<StoredMediaFolder> for MediaFolder {
+    fn from(stored: StoredMediaFolder) -> Self {
+        match stored {
+            StoredMediaFolder::Path(path) => Self::new(path),
+            StoredMediaFolder::Folder {
+                path,
+                exclude,
+                library_type,
+                metadata_language,
+                fetch_metadata,
+            } => Self {
+                path,
+                exclude,
+                library_type,
+                metadata_language,
+                fetch_metadata,
+            },
+        }
+    }
+}
+
+/// A single media entry found during scanning
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct MediaEntry {
+    /// Content fingerprint (see `fingerprint_file`), stable across renames and moves
     pub id: String,
     pub path: String,
     pub filename: String,
     pub extension: String,
     pub size_bytes: u64,
+    /// Set when the entry stands for a Blu-ray/DVD folder or a disc image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disc: Option<DiscInfo>,
+    /// Files of a multi-part title (CD1, CD2...) in playback order, `path` being the first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<String>,
+    /// Set for trailers, featurettes and other bonus material (see `extras`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<ExtraKind>,
+    /// External subtitle files found next to the video (see `subtitles`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtitles: Vec<SubtitleTrack>,
+    /// False while the folder holding the file is unreachable (unmounted drive or
+    /// share): the entry is kept as it was last seen
+    #[serde(default = "default_true")]
+    pub available: bool,
+    /// Unix time the file was first seen by a scan or the watcher, kept across
+    /// rescans, renames and moves
+    #[serde(default)]
+    pub added_at: i64,
+    /// Unix time of the last scan or watcher event that found the file
+    #[serde(default)]
+    pub last_seen_at: i64,
+    /// Modification time of the file (the main feature of a disc), Unix seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<i64>,
+    /// Creation time of the file, or its inode change time (ctime) on filesystems
+    /// that keep none, Unix seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
 }
 
 /// Result of a scan operation
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ScanResult {
     pub total_found: usize,
+    /// Entries that were not in the library before
+    pub added: usize,
+    /// Entries whose file changed (new content fingerprint or size)
+    pub changed: usize,
+    pub unchanged: usize,
+    /// Library entries whose file is gone
+    pub removed: usize,
     pub media_entries: Vec<MediaEntry>,
 }
 
@@ -51,14 +209,37 @@ fn get_settings_path(app: &AppHandle, user_id: &str) -> Result<PathBuf, String>
     Ok(get_user_dir(app, user_id)?.join("settings.json"))
 }
 
-/// Get path to the media library flat file (in the user's directory)
-fn get_library_path(app: &AppHandle, user_id: &str) -> Result<PathBuf, String> {
-    Ok(get_user_dir(app, user_id)?.join("library.json"))
+/// Load the media library (empty if nothing was scanned yet)
+pub fn load_library(app: &AppHandle, user_id: &str) -> Result<Vec<MediaEntry>, String> {
+    crate::db::load_entries(app, user_id)
 }
 
-/// Public accessor for watcher module
-pub fn get_library_path_public(app: &AppHandle, user_id: &str) -> Result<PathBuf, String> {
-    get_library_path(app, user_id)
+/// Write the changes from `previous` (the library as it was read) to `entries`, in
+/// one transaction. Only differing entries are written, so the ones another writer
+/// added or updated meanwhile are kept.
+pub fn save_library<'a>(
+    app: &AppHandle,
+    user_id: &str,
+    previous: impl IntoIterator<Item = &'a MediaEntry>,
+    entries: &[MediaEntry],
+) -> Result<(), String> {
+    let previous: HashMap<&str, &MediaEntry> =
+        previous.into_iter().map(|e| (e.path.as_str(), e)).collect();
+    let current: HashSet<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+
+    let removed: Vec<&str> = previous
+        .keys()
+        .copied()
+        .filter(|path| !current.contains(path))
+        .collect();
+    let upserted: Vec<&MediaEntry> = entries
+        .iter()
+        .filter(|e| previous.get(e.path.as_str()).is_none_or(|p| *p != *e))
+        .collect();
+    if removed.is_empty() && upserted.is_empty() {
+        return Ok(());
+    }
+    crate::db::update_entries(app, user_id, &removed, &upserted)
 }
 
 /// Public accessor for metadata module
@@ -110,6 +291,18 @@ pub struct Settings {
     /// How many months log files should be retained before being deleted (1-12)
     #[serde(default = "default_log_retention_months")]
     pub log_retention_months: u32,
+    /// Minutes of inactivity before the profile is locked again (0 = never)
+    #[serde(default = "default_auto_lock_minutes")]
+    pub auto_lock_minutes: u32,
+    /// Version played for titles available in several versions, unless one was picked
+    #[serde(default)]
+    pub default_version: VersionPreference,
+    /// Days the metadata of a missing entry is kept, in case its drive comes back
+    #[serde(default = "default_metadata_gc_grace_days")]
+    pub metadata_gc_grace_days: u32,
+    /// Maximum size of the metadata cache in MB (0 = unlimited)
+    #[serde(default)]
+    pub metadata_cache_max_mb: u32,
 }
 
 fn default_true() -> bool {
@@ -124,6 +317,14 @@ fn default_log_retention_months() -> u32 {
     3
 }
 
+fn default_auto_lock_minutes() -> u32 {
+    30
+}
+
+fn default_metadata_gc_grace_days() -> u32 {
+    30
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -133,12 +334,16 @@ impl Default for Settings {
             metadata_providers: default_metadata_providers(),
             metadata_cache_months: 1,
             log_retention_months: 3,
+            auto_lock_minutes: 30,
+            default_version: VersionPreference::default(),
+            metadata_gc_grace_days: 30,
+            metadata_cache_max_mb: 0,
         }
     }
 }
 
 /// Load settings from the user's settings.json
-fn load_settings(app: &AppHandle, user_id: &str) -> Result<Settings, String> {
+pub fn load_settings(app: &AppHandle, user_id: &str) -> Result<Settings, String> {
     let path = get_settings_path(app, user_id)?;
 
     if !path.exists() {
@@ -146,7 +351,7 @@ fn load_settings(app: &AppHandle, user_id: &str) -> Result<Settings, String> {
         return Ok(Settings::default());
     }
 
-    let raw = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let raw = crate::vault::read_to_string(app, user_id, &path)?;
     let settings: Settings = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
     Ok(settings)
 }
@@ -155,13 +360,14 @@ fn load_settings(app: &AppHandle, user_id: &str) -> Result<Settings, String> {
 fn save_settings(app: &AppHandle, user_id: &str, settings: &Settings) -> Result<(), String> {
     let path = get_settings_path(app, user_id)?;
     let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| e.to_string())?;
+    crate::vault::write(app, user_id, &path, json.as_bytes())?;
     Ok(())
 }
 
 /// Load the media configuration from the user's settings
 #[tauri::command]
 pub fn get_media_config(app: AppHandle, user_id: String) -> Result<MediaConfig, String> {
+    crate::session::require(&app, &user_id)?;
     let settings = load_settings(&app, &user_id)?;
 
     log::info!(
@@ -175,6 +381,7 @@ pub fn get_media_config(app: AppHandle, user_id: String) -> Result<MediaConfig,
 /// Save the media configuration to the user's settings
 #[tauri::command]
 pub fn save_media_config(app: AppHandle, user_id: String, config: MediaConfig) -> Result<(), String> {
+    crate::session::require(&app, &user_id)?;
     let mut settings = load_settings(&app, &user_id)?;
     settings.media = config;
     save_settings(&app, &user_id, &settings)?;
@@ -183,7 +390,12 @@ pub fn save_media_config(app: AppHandle, user_id: String, config: MediaConfig) -
         "[Media] Saved config with {} folder(s) for user {}: {:?}",
         settings.media.folders.len(),
         user_id,
-        settings.media.folders
+        settings
+            .media
+            .folders
+            .iter()
+            .map(|f| &f.path)
+            .collect::<Vec<_>>()
     );
     Ok(())
 }
@@ -191,12 +403,13 @@ pub fn save_media_config(app: AppHandle, user_id: String, config: MediaConfig) -
 /// Add a folder to the media configuration
 #[tauri::command]
 pub fn add_media_folder(app: AppHandle, user_id: String, folder: String) -> Result<MediaConfig, String> {
+    crate::session::require(&app, &user_id)?;
     let mut config = get_media_config(app.clone(), user_id.clone())?;
 
     // Normalize path separators
     let normalized = folder.replace('\\', "/");
 
-    if config.folders.contains(&normalized) {
+    if config.folders.iter().any(|f| f.path == normalized) {
         log::warn!("[Media] Folder already in config: {}", normalized);
         return Err("This folder is already in the list.".to_string());
     }
@@ -207,7 +420,7 @@ pub fn add_media_folder(app: AppHandle, user_id: String, folder: String) -> Resu
         return Err("The specified folder does not exist.".to_string());
     }
 
-    config.folders.push(normalized.clone());
+    config.folders.push(MediaFolder::new(normalized.clone()));
     save_media_config(app.clone(), user_id.clone(), config.clone())?;
 
     // Restart watcher to include the new folder
@@ -222,11 +435,12 @@ pub fn add_media_folder(app: AppHandle, user_id: String, folder: String) -> Resu
 /// Remove a folder from the media configuration
 #[tauri::command]
 pub fn remove_media_folder(app: AppHandle, user_id: String, folder: String) -> Result<MediaConfig, String> {
+    crate::session::require(&app, &user_id)?;
     let mut config = get_media_config(app.clone(), user_id.clone())?;
 
     let normalized = folder.replace('\\', "/");
     let initial_len = config.folders.len();
-    config.folders.retain(|f| f != &normalized);
+    config.folders.retain(|f| f.path != normalized);
 
     if config.folders.len() == initial_len {
         log::warn!("[Media] Folder not found in config: {}", normalized);
@@ -244,55 +458,790 @@ pub fn remove_media_folder(app: AppHandle, user_id: String, folder: String) -> R
     Ok(config)
 }
 
-/// Recursively scan a directory for media files
-fn scan_directory(dir: &std::path::Path, entries: &mut Vec<MediaEntry>) {
-    let read_dir = match fs::read_dir(dir) {
-        Ok(rd) => rd,
+/// Replace the exclusion patterns of a configured folder
+#[tauri::command]
+pub fn set_folder_exclusions(
+    app: AppHandle,
+    user_id: String,
+    folder: String,
+    patterns: Vec<String>,
+) -> Result<MediaConfig, String> {
+    crate::session::require(&app, &user_id)?;
+    let mut config = get_media_config(app.clone(), user_id.clone())?;
+
+    let patterns: Vec<String> = patterns
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    crate::exclusions::validate_patterns(&patterns)?;
+
+    let normalized = folder.replace('\\', "/");
+    let entry = config
+        .folders
+        .iter_mut()
+        .find(|f| f.path == normalized)
+        .ok_or_else(|| "Folder not found in configuration.".to_string())?;
+    entry.exclude = patterns;
+
+    save_media_config(app.clone(), user_id.clone(), config.clone())?;
+
+    // Restart watcher so it filters events with the new rules
+    if let Err(e) = crate::watcher::restart_watching(&app, &user_id) {
+        log::warn!(
+            "[Media] Failed to restart watcher after updating exclusions: {}",
+            e
+        );
+    }
+
+    log::info!("[Media] Updated exclusions for folder: {}", normalized);
+    Ok(config)
+}
+
+/// Change the library type and metadata options of a configured folder.
+/// Metadata of its entries is fetched again when the type or language changed.
+#[tauri::command]
+pub fn set_folder_options(
+    app: AppHandle,
+    user_id: String,
+    folder: String,
+    library_type: LibraryType,
+    metadata_language: Option<String>,
+    fetch_metadata: bool,
+) -> Result<MediaConfig, String> {
+    crate::session::require(&app, &user_id)?;
+    let mut config = get_media_config(app.clone(), user_id.clone())?;
+
+    let metadata_language = metadata_language
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty());
+    if let Some(language) = &metadata_language {
+        let valid = language.len() <= 8
+            && language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err("Invalid metadata language.".to_string());
+        }
+    }
+
+    let normalized = folder.replace('\\', "/");
+    let entry = config
+        .folders
+        .iter_mut()
+        .find(|f| f.path == normalized)
+        .ok_or_else(|| "Folder not found in configuration.".to_string())?;
+    let invalidate =
+        entry.library_type != library_type || entry.metadata_language != metadata_language;
+    let refetch = invalidate || (fetch_metadata && !entry.fetch_metadata);
+    entry.library_type = library_type;
+    entry.metadata_language = metadata_language;
+    entry.fetch_metadata = fetch_metadata;
+
+    save_media_config(app.clone(), user_id.clone(), config.clone())?;
+
+    if invalidate {
+        let entries: Vec<MediaEntry> = load_library(&app, &user_id)?
+            .into_iter()
+            .filter(|e| Path::new(&e.path).starts_with(&normalized))
+            .collect();
+        crate::metadata::invalidate_metadata(&app, &user_id, &entries);
+    }
+    if refetch {
+        let app_clone = app.clone();
+        let user_id_clone = user_id.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = crate::metadata::fetch_missing_metadata(&app_clone, &user_id_clone) {
+                log::warn!(
+                    "[Media] Failed to fetch metadata after folder update: {}",
+                    e
+                );
+            }
+        });
+    }
+
+    log::info!(
+        "[Media] Updated options for folder {}: {:?}, fetch={}",
+        normalized,
+        library_type,
+        fetch_metadata
+    );
+    Ok(config)
+}
+
+/// Compute a content fingerprint from the file size and SHA-256 hashes of the first
+/// and last chunks. Cheap even on large files, and unchanged by renames and moves.
+pub fn fingerprint_file(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let mut chunk = vec![0u8; FINGERPRINT_CHUNK_BYTES.min(size) as usize];
+    file.read_exact(&mut chunk).map_err(|e| e.to_string())?;
+    hasher.update(&chunk);
+
+    if size > FINGERPRINT_CHUNK_BYTES {
+        file.seek(SeekFrom::Start(size - FINGERPRINT_CHUNK_BYTES))
+            .map_err(|e| e.to_string())?;
+        file.read_exact(&mut chunk).map_err(|e| e.to_string())?;
+        hasher.update(&chunk);
+    }
+
+    let digest = hasher.finalize();
+    Ok(digest[..16].iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Create a MediaEntry for a video file, with its subtitles (None for other files
+/// or unreadable ones)
+pub fn entry_from_file(path: &Path) -> Option<MediaEntry> {
+    if !path.is_file() {
+        return None;
+    }
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if !VIDEO_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+
+    let id = match fingerprint_file(path) {
+        Ok(id) => id,
+        Err(e) => {
+            log::warn!("[Media] Cannot fingerprint {:?}: {}", path, e);
+            return None;
+        }
+    };
+    Some(MediaEntry {
+        subtitles: crate::subtitles::discover(path),
+        ..entry_with_id(path, id)
+    })
+}
+
+/// Create a MediaEntry for a video file whose fingerprint is already known
+fn entry_with_id(path: &Path, id: String) -> MediaEntry {
+    let meta = fs::metadata(path).ok();
+    let size = meta.as_ref().map(|m| m.len()).unwrap_or(0);
+    let (modified_at, created_at) = meta.as_ref().map(file_times).unwrap_or_default();
+    let now = chrono::Utc::now().timestamp();
+    let filename = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let normalized = path.to_string_lossy().replace('\\', "/");
+
+    // A disc image is played as a whole
+    let disc = (extension == "iso").then(|| DiscInfo {
+        format: DiscFormat::Iso,
+        main_feature: vec![normalized.clone()],
+    });
+
+    MediaEntry {
+        id,
+        path: normalized,
+        filename,
+        extension,
+        size_bytes: size,
+        disc,
+        parts: vec![],
+        extra: crate::extras::extra_kind(path),
+        subtitles: vec![],
+        available: true,
+        added_at: now,
+        last_seen_at: now,
+        modified_at,
+        created_at,
+    }
+}
+
+/// Create a single MediaEntry for a Blu-ray or DVD folder, identified by its main
+/// feature and titled after the folder (None if no main feature is found)
+pub fn entry_from_disc(root: &Path) -> Option<MediaEntry> {
+    let format = disc::disc_format_of(root)?;
+    let (streams, size) = disc::main_feature(root, format)?;
+    let id = match fingerprint_file(&streams[0]) {
+        Ok(id) => id,
+        Err(e) => {
+            log::warn!("[Media] Cannot fingerprint {:?}: {}", streams[0], e);
+            return None;
+        }
+    };
+    Some(disc_entry_with_id(root, format, &streams, size, id))
+}
+
+fn disc_entry_with_id(
+    root: &Path,
+    format: DiscFormat,
+    streams: &[PathBuf],
+    size: u64,
+    id: String,
+) -> MediaEntry {
+    let filename = root
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let (modified_at, created_at) = fs::metadata(&streams[0])
+        .map(|m| file_times(&m))
+        .unwrap_or_default();
+    let now = chrono::Utc::now().timestamp();
+    MediaEntry {
+        id,
+        path: root.to_string_lossy().replace('\\', "/"),
+        filename,
+        extension: format.extension().to_string(),
+        size_bytes: size,
+        disc: Some(DiscInfo {
+            format,
+            main_feature: streams
+                .iter()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .collect(),
+        }),
+        parts: vec![],
+        extra: crate::extras::extra_kind(root),
+        subtitles: vec![],
+        available: true,
+        added_at: now,
+        last_seen_at: now,
+        modified_at,
+        created_at,
+    }
+}
+
+/// Part marker of a multi-part file: "Movie.2001.CD1.avi", "Movie - part2.mkv".
+/// The marker starts a word, so "Concept 1.mkv" or "Counterpart 2.mkv" are not parts.
+fn stacking_regex() -> &'static Regex {
+    static STACKING: OnceLock<Regex> = OnceLock::new();
+    STACKING.get_or_init(|| {
+        Regex::new(r"(?i)^(.+?)[ _.\-]+(?:cd|dvd|part|pt|disc|disk)[ _.\-]*(\d{1,2})(.*)\.([^.]+)$")
+            .unwrap()
+    })
+}
+
+/// A file recognized as one part of a multi-part title
+struct StackPart {
+    /// Directory, name without the part marker and extension: equal for all parts
+    key: String,
+    number: u32,
+    /// Filename without the part marker ("Movie.2001.avi")
+    stacked_name: String,
+}
+
+fn stack_part(path: &Path) -> Option<StackPart> {
+    let filename = path.file_name()?.to_str()?;
+    let caps = stacking_regex().captures(filename)?;
+    let (prefix, suffix, ext) = (&caps[1], &caps[3], &caps[4]);
+    let dir = path
+        .parent()
+        .map(|p| p.to_string_lossy().replace('\\', "/"));
+    Some(StackPart {
+        key: format!("{}/{}|{}|{}", dir.unwrap_or_default(), prefix, suffix, ext).to_lowercase(),
+        number: caps[2].parse().ok()?,
+        stacked_name: format!("{}{}.{}", prefix, suffix, ext),
+    })
+}
+
+/// Whether a file name carries a part marker (CD1, part2...)
+pub fn is_stack_part(path: &Path) -> bool {
+    stack_part(path).is_some()
+}
+
+/// Merge the parts of multi-part titles into one entry each. Parts must sit in the
+/// same folder, share their name apart from the marker, and be numbered from 1
+/// without gaps. The merged entry keeps the ID and path of the first part.
+pub fn stack_parts(entries: Vec<MediaEntry>) -> Vec<MediaEntry> {
+    let mut groups: HashMap<String, Vec<(u32, usize, String)>> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.disc.is_some() {
+            continue;
+        }
+        if let Some(part) = stack_part(Path::new(&entry.path)) {
+            groups
+                .entry(part.key)
+                .or_default()
+                .push((part.number, index, part.stacked_name));
+        }
+    }
+
+    let mut merged = HashSet::new();
+    let mut stacked = Vec::new();
+    for mut parts in groups.into_values() {
+        parts.sort_by_key(|(number, _, _)| *number);
+        let sequential = parts
+            .iter()
+            .enumerate()
+            .all(|(i, (number, _, _))| *number == i as u32 + 1);
+        if parts.len() < 2 || !sequential {
+            continue;
+        }
+
+        let first = &entries[parts[0].1];
+        stacked.push(MediaEntry {
+            filename: parts[0].2.clone(),
+            size_bytes: parts.iter().map(|(_, i, _)| entries[*i].size_bytes).sum(),
+            parts: parts
+                .iter()
+                .map(|(_, i, _)| entries[*i].path.clone())
+                .collect(),
+            ..first.clone()
+        });
+        merged.extend(parts.iter().map(|(_, i, _)| *i));
+    }
+
+    entries
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !merged.contains(i))
+        .map(|(_, entry)| entry)
+        .chain(stacked)
+        .collect()
+}
+
+/// Give identical copies of the same file distinct IDs. Entries must be sorted by
+/// path: the first copy keeps the plain fingerprint, the others get a path-based suffix.
+pub fn assign_unique_ids(entries: &mut [MediaEntry]) {
+    let mut seen = HashSet::new();
+    for entry in entries.iter_mut() {
+        if !seen.insert(entry.id.clone()) {
+            let path_hash = Sha256::digest(entry.path.as_bytes());
+            let suffix: String = path_hash[..4]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            entry.id = format!("{}-{}", entry.id, suffix);
+            seen.insert(entry.id.clone());
+        }
+    }
+}
+
+/// Cached state of a scanned video file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct FileState {
+    size: u64,
+    mtime_ms: u64,
+    inode: u64,
+    /// Content fingerprint computed for this size/mtime/inode
+    fingerprint: String,
+}
+
+/// Cached listing of a scanned directory, valid while its mtime and inode are unchanged
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct DirState {
+    mtime_ms: u64,
+    inode: u64,
+    /// Names of the video files directly inside
+    files: Vec<String>,
+    /// Names of the subdirectories
+    dirs: Vec<String>,
+    /// Names of the subtitle files directly inside (None in caches written before
+    /// subtitles were listed, which are then listed again)
+    #[serde(default)]
+    subtitles: Option<Vec<String>>,
+}
+
+/// Per-file and per-directory state persisted between scans (scan_cache.json)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct ScanCache {
+    #[serde(default)]
+    dirs: HashMap<String, DirState>,
+    #[serde(default)]
+    files: HashMap<String, FileState>,
+}
+
+/// Get path to the scan cache file (in the user's directory)
+fn get_scan_cache_path(app: &AppHandle, user_id: &str) -> Result<PathBuf, String> {
+    Ok(get_user_dir(app, user_id)?.join("scan_cache.json"))
+}
+
+/// Load the scan cache (empty if missing or unreadable: the next scan rebuilds it)
+fn load_scan_cache(app: &AppHandle, user_id: &str) -> ScanCache {
+    get_scan_cache_path(app, user_id)
+        .and_then(|path| crate::vault::read_to_string(app, user_id, &path))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_scan_cache(app: &AppHandle, user_id: &str, cache: &ScanCache) -> Result<(), String> {
+    let path = get_scan_cache_path(app, user_id)?;
+    let json = serde_json::to_string(cache).map_err(|e| e.to_string())?;
+    crate::vault::write(app, user_id, &path, json.as_bytes())
+}
+
+/// Modification time in milliseconds since the epoch (0 if unavailable)
+fn mtime_ms(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Modification and creation times in Unix seconds. Where the filesystem keeps
+/// no creation time, the inode change time stands in.
+fn file_times(meta: &fs::Metadata) -> (Option<i64>, Option<i64>) {
+    let seconds = |time: std::io::Result<std::time::SystemTime>| {
+        time.ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+    };
+    let created = seconds(meta.created()).or_else(|| change_time(meta));
+    (seconds(meta.modified()), created)
+}
+
+#[cfg(unix)]
+fn change_time(meta: &fs::Metadata) -> Option<i64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.ctime())
+}
+
+#[cfg(not(unix))]
+fn change_time(_meta: &fs::Metadata) -> Option<i64> {
+    None
+}
+
+/// Inode number, used to notice files replaced by another one (0 where unsupported)
+#[cfg(unix)]
+fn inode(meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_meta: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Working state of a scan: the previous cache, the cache being rebuilt and the results
+struct ScanContext<'a> {
+    previous_cache: &'a ScanCache,
+    previous_entries: &'a HashMap<String, MediaEntry>,
+    /// The same entries by ID, to follow renamed and moved files
+    previous_ids: HashMap<&'a str, &'a MediaEntry>,
+    cache: ScanCache,
+    entries: Vec<MediaEntry>,
+    /// Directories whose listing came from the cache
+    cached_dirs: usize,
+    /// Files whose content had to be fingerprinted again
+    hashed_files: usize,
+    /// Files and directories skipped by exclusion rules
+    ignored: usize,
+}
+
+/// Recursively scan a directory for media files.
+/// A directory whose mtime and inode are unchanged is not listed again. Its files
+/// are still stat'ed, as overwriting a file in place leaves the directory as it
+/// was, but a file whose size, mtime and inode are unchanged is not fingerprinted again.
+fn scan_directory(dir: &std::path::Path, rules: &IgnoreRules, ctx: &mut ScanContext) {
+    let dir_meta = match fs::metadata(dir) {
+        Ok(meta) => meta,
         Err(e) => {
             log::warn!("[Media] Cannot read directory {:?}: {}", dir, e);
             return;
         }
     };
+    let dir_key = dir.to_string_lossy().replace('\\', "/");
+    let (dir_mtime, dir_inode) = (mtime_ms(&dir_meta), inode(&dir_meta));
+
+    let listing = match ctx.previous_cache.dirs.get(&dir_key) {
+        Some(cached)
+            if dir_mtime != 0
+                && cached.mtime_ms == dir_mtime
+                && cached.inode == dir_inode
+                && cached.subtitles.is_some() =>
+        {
+            ctx.cached_dirs += 1;
+            cached.clone()
+        }
+        _ => {
+            let read_dir = match fs::read_dir(dir) {
+                Ok(rd) => rd,
+                Err(e) => {
+                    log::warn!("[Media] Cannot read directory {:?}: {}", dir, e);
+                    return;
+                }
+            };
+            let mut listing = DirState {
+                mtime_ms: dir_mtime,
+                inode: dir_inode,
+                files: vec![],
+                dirs: vec![],
+                subtitles: None,
+            };
+            let mut subtitles = vec![];
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().to_string();
+                if path.is_dir() {
+                    listing.dirs.push(name);
+                } else if path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+                {
+                    listing.files.push(name);
+                } else if crate::subtitles::is_subtitle(&path) {
+                    subtitles.push(name);
+                }
+            }
+            listing.subtitles = Some(subtitles);
+            listing
+        }
+    };
 
-    for entry in read_dir.flatten() {
-        let path = entry.path();
+    // A Blu-ray/DVD folder is one title: its disc folders are not scanned on their own
+    let disc_format = disc::disc_format(&listing.dirs);
+    if let Some(format) = disc_format {
+        scan_disc(dir, format, ctx);
+    }
+
+    let sidecars = Sidecars::list(
+        dir,
+        listing.subtitles.as_deref().unwrap_or_default(),
+        &listing.dirs,
+    );
+    let lone_video = listing.files.len() == 1;
+
+    // The listing is cached unfiltered, so rule changes apply without a re-list
+    let rules = rules.enter(dir);
+    for name in &listing.files {
+        let path = dir.join(name);
+        if rules.is_ignored(&path, false) {
+            ctx.ignored += 1;
+            continue;
+        }
+        scan_file(&path, sidecars.tracks_for(&path, lone_video), ctx);
+    }
+    for name in &listing.dirs {
+        let path = dir.join(name);
+        if disc_format.is_some() && disc::is_disc_folder(name) {
+            continue;
+        }
+        if rules.is_ignored(&path, true) {
+            log::debug!("[Media] Skipping excluded directory: {}", path.display());
+            ctx.ignored += 1;
+            continue;
+        }
+        scan_directory(&path, &rules, ctx);
+    }
+    ctx.cache.dirs.insert(dir_key, listing);
+}
 
-        if path.is_dir() {
-            scan_directory(&path, entries);
-        } else if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                let ext_lower = ext.to_lowercase();
-                if VIDEO_EXTENSIONS.contains(&ext_lower.as_str()) {
-                    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
-                    let filename = path
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let file_path = path.to_string_lossy().replace('\\', "/");
-
-                    entries.push(MediaEntry {
-                        id: Uuid::new_v4().to_string(),
-                        path: file_path,
-                        filename,
-                        extension: ext_lower,
-                        size_bytes: size,
-                    });
+/// Fingerprint of a file, reused from the previous scan when its size, mtime and
+/// inode are unchanged. The second value tells whether it came from the cache.
+fn cached_fingerprint(path: &Path, ctx: &mut ScanContext) -> Option<(String, bool)> {
+    let meta = match fs::metadata(path) {
+        Ok(meta) if meta.is_file() => meta,
+        _ => return None,
+    };
+    let key = path.to_string_lossy().replace('\\', "/");
+    let (size, mtime, ino) = (meta.len(), mtime_ms(&meta), inode(&meta));
+
+    let cached = ctx
+        .previous_cache
+        .files
+        .get(&key)
+        .filter(|c| c.size == size && c.mtime_ms == mtime && c.inode == ino)
+        .map(|c| c.fingerprint.clone());
+    let from_cache = cached.is_some();
+    let fingerprint = match cached {
+        Some(fingerprint) => fingerprint,
+        None => {
+            ctx.hashed_files += 1;
+            match fingerprint_file(path) {
+                Ok(fingerprint) => fingerprint,
+                Err(e) => {
+                    log::warn!("[Media] Cannot fingerprint {:?}: {}", path, e);
+                    return None;
                 }
             }
         }
+    };
+
+    ctx.cache.files.insert(
+        key,
+        FileState {
+            size,
+            mtime_ms: mtime,
+            inode: ino,
+            fingerprint: fingerprint.clone(),
+        },
+    );
+    Some((fingerprint, from_cache))
+}
+
+/// Add a video file to the scan results, reusing its cached fingerprint when unchanged
+fn scan_file(path: &Path, subtitles: Vec<SubtitleTrack>, ctx: &mut ScanContext) {
+    let (id, from_cache) = match cached_fingerprint(path, ctx) {
+        Some(result) => result,
+        None => return,
+    };
+    let key = path.to_string_lossy().replace('\\', "/");
+
+    // Stacked entries are rebuilt from their parts after the scan. The times
+    // of an unchanged file are refreshed for entries saved before they existed.
+    let previous = ctx.previous_entries.get(&key);
+    let entry = match previous {
+        Some(previous) if from_cache && previous.parts.is_empty() => {
+            let (modified_at, created_at) = match previous.modified_at {
+                Some(_) => (previous.modified_at, previous.created_at),
+                None => fs::metadata(path)
+                    .map(|m| file_times(&m))
+                    .unwrap_or_default(),
+            };
+            MediaEntry {
+                id,
+                extra: crate::extras::extra_kind(path),
+                subtitles,
+                available: true,
+                modified_at,
+                created_at,
+                ..previous.clone()
+            }
+        }
+        _ => {
+            let moved = ctx.previous_ids.get(id.as_str()).copied();
+            with_history(
+                MediaEntry {
+                    subtitles,
+                    ..entry_with_id(path, id)
+                },
+                previous.into_iter().chain(moved),
+            )
+        }
+    };
+    ctx.entries.push(entry);
+}
+
+/// Add a Blu-ray/DVD folder to the scan results as a single entry
+fn scan_disc(root: &Path, format: DiscFormat, ctx: &mut ScanContext) {
+    let (streams, size) = match disc::main_feature(root, format) {
+        Some(feature) => feature,
+        None => {
+            log::warn!("[Media] No main feature found in disc folder {:?}", root);
+            return;
+        }
+    };
+    let (id, _) = match cached_fingerprint(&streams[0], ctx) {
+        Some(result) => result,
+        None => return,
+    };
+    log::debug!(
+        "[Media] Disc folder {:?}: {:?}, main feature {:?}",
+        root,
+        format,
+        streams[0]
+    );
+    let key = root.to_string_lossy().replace('\\', "/");
+    let previous = ctx.previous_entries.get(&key);
+    let moved = ctx.previous_ids.get(id.as_str()).copied();
+    let entry = disc_entry_with_id(root, format, &streams, size, id);
+    ctx.entries
+        .push(with_history(entry, previous.into_iter().chain(moved)));
+}
+
+/// Keep the dates of the entries previously at the same path or with the same
+/// content (a renamed or moved file), as the database does: the earliest
+/// `added_at` wins. `last_seen_at` is moved forward for the whole library once
+/// the scan is saved.
+fn with_history<'a>(
+    entry: MediaEntry,
+    previous: impl IntoIterator<Item = &'a MediaEntry>,
+) -> MediaEntry {
+    let previous: Vec<&MediaEntry> = previous.into_iter().collect();
+    let added_at = previous.iter().map(|p| p.added_at).min();
+    let last_seen_at = previous.iter().map(|p| p.last_seen_at).max();
+    match (added_at, last_seen_at) {
+        (Some(added_at), Some(last_seen_at)) => MediaEntry {
+            added_at,
+            last_seen_at,
+            ..entry
+        },
+        _ => entry,
     }
 }
 
-/// Scan all configured folders and update the media library flat file
+/// Whether a library folder can be scanned. An empty folder that held media is
+/// taken for an unmounted mount point, not for a folder whose files were all
+/// deleted, unless a drive is mounted on it.
+pub fn is_folder_available<'a>(
+    config: &MediaConfig,
+    folder: &MediaFolder,
+    entries: impl IntoIterator<Item = &'a MediaEntry>,
+) -> bool {
+    let path = Path::new(&folder.path);
+    let mut listing = match fs::read_dir(path) {
+        Ok(listing) => listing,
+        Err(_) => return false,
+    };
+    if listing.next().is_some() || is_mount_point(path) {
+        return true;
+    }
+    !entries.into_iter().any(|e| {
+        config
+            .folder_for(Path::new(&e.path))
+            .is_some_and(|f| f.path == folder.path)
+    })
+}
+
+/// Whether a filesystem is mounted on a directory: it is on another device than its parent
+#[cfg(unix)]
+fn is_mount_point(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let parent = match path.parent() {
+        Some(parent) => parent,
+        None => return true,
+    };
+    match (fs::metadata(path), fs::metadata(parent)) {
+        (Ok(dir), Ok(parent)) => dir.dev() != parent.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_mount_point(_path: &Path) -> bool {
+    false
+}
+
+/// Library folders that cannot be reached right now
+#[tauri::command]
+pub fn get_offline_folders(app: AppHandle, user_id: String) -> Result<Vec<String>, String> {
+    crate::session::require(&app, &user_id)?;
+    let config = load_settings(&app, &user_id)?.media;
+    let entries = load_library(&app, &user_id).unwrap_or_default();
+    Ok(config
+        .folders
+        .iter()
+        .filter(|f| !is_folder_available(&config, f, &entries))
+        .map(|f| f.path.clone())
+        .collect())
+}
+
+/// Scan all configured folders and update the media library
 #[tauri::command]
 pub fn scan_media_folders(app: AppHandle, user_id: String) -> Result<ScanResult, String> {
-    let config = get_media_config(app.clone(), user_id.clone())?;
+    crate::session::require(&app, &user_id)?;
+    scan_library(&app, &user_id)
+}
+
+/// Scan implementation shared by the scan command and the watcher's startup scan
+pub fn scan_library(app: &AppHandle, user_id: &str) -> Result<ScanResult, String> {
+    let config = load_settings(app, user_id)?.media;
 
     if config.folders.is_empty() {
         log::info!("[Media] No folders configured, nothing to scan");
         return Ok(ScanResult {
             total_found: 0,
+            added: 0,
+            changed: 0,
+            unchanged: 0,
+            removed: 0,
             media_entries: vec![],
         });
     }
@@ -302,87 +1251,186 @@ pub fn scan_media_folders(app: AppHandle, user_id: String) -> Result<ScanResult,
         config.folders.len()
     );
 
-    let mut all_entries: Vec<MediaEntry> = Vec::new();
+    let previous_entries: HashMap<String, MediaEntry> = load_library(app, user_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|e| (e.path.clone(), e))
+        .collect();
+    let previous_cache = load_scan_cache(app, user_id);
+    let mut ctx = ScanContext {
+        previous_cache: &previous_cache,
+        previous_entries: &previous_entries,
+        previous_ids: previous_entries
+            .values()
+            .map(|e| (e.id.as_str(), e))
+            .collect(),
+        cache: ScanCache::default(),
+        entries: Vec::new(),
+        cached_dirs: 0,
+        hashed_files: 0,
+        ignored: 0,
+    };
 
+    let mut offline: Vec<&MediaFolder> = Vec::new();
     for folder in &config.folders {
-        let path = std::path::Path::new(folder);
-        if path.is_dir() {
-            log::info!("[Media] Scanning folder: {}", folder);
-            scan_directory(path, &mut all_entries);
+        let path = std::path::Path::new(&folder.path);
+        if is_folder_available(&config, folder, previous_entries.values()) {
+            log::info!("[Media] Scanning folder: {}", folder.path);
+            let rules = IgnoreRules::for_folder(folder, config.default_exclusions);
+            scan_directory(path, &rules, &mut ctx);
         } else {
-            log::warn!("[Media] Skipping non-existent folder: {}", folder);
+            log::warn!(
+                "[Media] Folder unavailable, keeping its entries offline: {}",
+                folder.path
+            );
+            offline.push(folder);
         }
     }
 
-    // Deduplicate by path
+    let ScanContext {
+        mut cache,
+        entries: all_entries,
+        cached_dirs,
+        hashed_files,
+        ignored,
+        ..
+    } = ctx;
+
+    // Group multi-part titles, then deduplicate by path
+    let mut all_entries = stack_parts(all_entries);
+
+    // Entries of unreachable folders stay, with their cached state, until the
+    // folder comes back and they are reconciled by path and fingerprint
+    let is_offline = |path: &str| {
+        config
+            .folder_for(Path::new(path))
+            .is_some_and(|f| offline.iter().any(|o| o.path == f.path))
+    };
+    if !offline.is_empty() {
+        all_entries.extend(
+            previous_entries
+                .values()
+                .filter(|e| is_offline(&e.path))
+                .map(|e| MediaEntry {
+                    available: false,
+                    ..e.clone()
+                }),
+        );
+        for (key, dir) in &previous_cache.dirs {
+            if is_offline(key) {
+                cache.dirs.insert(key.clone(), dir.clone());
+            }
+        }
+        for (key, file) in &previous_cache.files {
+            if is_offline(key) {
+                cache.files.insert(key.clone(), file.clone());
+            }
+        }
+    }
     all_entries.sort_by(|a, b| a.path.cmp(&b.path));
     all_entries.dedup_by(|a, b| a.path == b.path);
-
-    // Preserve existing UUIDs for known paths
-    let library_path = get_library_path(&app, &user_id)?;
-    if library_path.exists() {
-        if let Ok(raw) = fs::read_to_string(&library_path) {
-            if let Ok(existing) = serde_json::from_str::<Vec<MediaEntry>>(&raw) {
-                let existing_map: std::collections::HashMap<String, String> = existing
-                    .into_iter()
-                    .filter(|e| !e.id.is_empty())
-                    .map(|e| (e.path.clone(), e.id))
-                    .collect();
-                for entry in &mut all_entries {
-                    if let Some(existing_id) = existing_map.get(&entry.path) {
-                        entry.id = existing_id.clone();
-                    }
-                }
+    assign_unique_ids(&mut all_entries);
+
+    // Compare with the previous library, and keep metadata attached to files
+    // that moved, changed, or still have an old random ID
+    let (mut added, mut changed, mut unchanged) = (0, 0, 0);
+    for entry in &all_entries {
+        let previous = previous_entries.get(&entry.path);
+        match previous {
+            Some(p)
+                if p.id == entry.id
+                    && p.size_bytes == entry.size_bytes
+                    && p.subtitles == entry.subtitles
+                    && p.available == entry.available =>
+            {
+                unchanged += 1;
+                continue;
             }
+            Some(_) => changed += 1,
+            None => added += 1,
+        }
+        if let Err(e) =
+            crate::metadata::reattach_metadata(app, user_id, previous.map(|p| p.id.as_str()), entry)
+        {
+            log::warn!(
+                "[Media] Failed to reattach metadata for {}: {}",
+                entry.path,
+                e
+            );
         }
     }
+    let seen: HashSet<&str> = all_entries.iter().map(|e| e.path.as_str()).collect();
+    let removed = previous_entries
+        .keys()
+        .filter(|path| !seen.contains(path.as_str()))
+        .count();
 
-    // Collect new entries (those that got a fresh UUID) for metadata fetching
-
-    let result = ScanResult {
+    let mut result = ScanResult {
         total_found: all_entries.len(),
+        added,
+        changed,
+        unchanged,
+        removed,
         media_entries: all_entries,
     };
 
-    // Write the media library flat file
-    let json = serde_json::to_string_pretty(&result.media_entries).map_err(|e| e.to_string())?;
-    fs::write(&library_path, json).map_err(|e| e.to_string())?;
+    save_library(
+        app,
+        user_id,
+        previous_entries.values(),
+        &result.media_entries,
+    )?;
+    let now = chrono::Utc::now().timestamp();
+    crate::db::mark_seen(app, user_id, now)?;
+    for entry in result.media_entries.iter_mut().filter(|e| e.available) {
+        entry.last_seen_at = now;
+    }
+    if let Err(e) = save_scan_cache(app, user_id, &cache) {
+        log::warn!("[Media] Failed to save scan cache: {}", e);
+    }
 
-    // Trigger metadata fetch for entries missing metadata
+    // Trigger metadata fetch for entries missing metadata, then drop the
+    // metadata of entries gone from the library
     {
         let app_clone = app.clone();
-        let user_id_clone = user_id.clone();
+        let user_id_clone = user_id.to_string();
         std::thread::spawn(move || {
             if let Err(e) = crate::metadata::fetch_missing_metadata(&app_clone, &user_id_clone) {
                 log::warn!("[Media] Failed to fetch metadata after scan: {}", e);
             }
+            if let Err(e) = crate::metadata::collect_garbage(&app_clone, &user_id_clone) {
+                log::warn!(
+                    "[Media] Failed to collect metadata garbage after scan: {}",
+                    e
+                );
+            }
         });
     }
 
     log::info!(
-        "[Media] Scan complete: {} media file(s) found, library saved to {:?}",
+        "[Media] Scan complete for user {}: {} media file(s), +{} ~{} ={} -{} ({} cached dir(s), {} file(s) hashed, {} excluded)",
+        user_id,
         result.total_found,
-        library_path
+        result.added,
+        result.changed,
+        result.unchanged,
+        result.removed,
+        cached_dirs,
+        hashed_files,
+        ignored
     );
 
     Ok(result)
 }
 
-/// Get the current media library from the flat file (without re-scanning)
+/// Get the current media library from the database (without re-scanning)
 #[tauri::command]
 pub fn get_media_library(app: AppHandle, user_id: String) -> Result<Vec<MediaEntry>, String> {
-    let library_path = get_library_path(&app, &user_id)?;
-
-    if !library_path.exists() {
-        log::info!("[Media] No media library file found, returning empty list");
-        return Ok(vec![]);
-    }
-
-    let raw = fs::read_to_string(&library_path).map_err(|e| e.to_string())?;
-    let entries: Vec<MediaEntry> = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+    crate::session::require(&app, &user_id)?;
+    let entries = load_library(&app, &user_id)?;
 
     log::info!(
-        "[Media] Loaded {} media entries from library file",
+        "[Media] Loaded {} media entries from the library database",
         entries.len()
     );
     Ok(entries)
@@ -411,13 +1459,16 @@ pub fn pick_folder() -> Result<Option<String>, String> {
 /// Get the full settings for a user
 #[tauri::command]
 pub fn get_settings(app: AppHandle, user_id: String) -> Result<Settings, String> {
+    crate::session::require(&app, &user_id)?;
     load_settings(&app, &user_id)
 }
 
 /// Update settings for a user
 #[tauri::command]
 pub fn update_settings(app: AppHandle, user_id: String, settings: Settings) -> Result<(), String> {
+    crate::session::require(&app, &user_id)?;
     save_settings(&app, &user_id, &settings)?;
+    crate::session::set_auto_lock(&app, &user_id, settings.auto_lock_minutes);
     log::info!("[Media] Settings updated for user {}: scan_on_startup={}, live_scan={}", user_id, settings.scan_on_startup, settings.live_scan);
     Ok(())
 }
@@ -425,24 +1476,39 @@ pub fn update_settings(app: AppHandle, user_id: String, settings: Settings) -> R
 /// Manually trigger metadata fetch for all entries missing metadata
 #[tauri::command]
 pub fn fetch_all_metadata(app: AppHandle, user_id: String) -> Result<String, String> {
+    crate::session::require(&app, &user_id)?;
     crate::metadata::fetch_missing_metadata(&app, &user_id)
 }
 
+/// Delete orphaned metadata and shrink the metadata cache to its maximum size
+#[tauri::command]
+pub fn collect_metadata_garbage(
+    app: AppHandle,
+    user_id: String,
+) -> Result<crate::metadata::MetadataGcResult, String> {
+    crate::session::require(&app, &user_id)?;
+    crate::metadata::collect_garbage(&app, &user_id)
+}
+
 /// Get metadata for a specific media entry
 #[tauri::command]
 pub fn get_media_metadata(app: AppHandle, user_id: String, media_id: String) -> Result<serde_json::Value, String> {
+    crate::session::require(&app, &user_id)?;
     crate::metadata::get_metadata(&app, &user_id, &media_id)
 }
 
 /// Start the file watcher for a user (called after login)
 #[tauri::command]
 pub fn start_media_watcher(app: AppHandle, user_id: String) -> Result<(), String> {
+    crate::session::require(&app, &user_id)?;
     crate::watcher::start_watching(&app, &user_id)
 }
 
-/// Stop the file watcher for a user (called on logout)
+/// Stop the file watcher for a user (live scan turned off). Closing the
+/// session stops it too.
 #[tauri::command]
 pub fn stop_media_watcher(app: AppHandle, user_id: String) -> Result<(), String> {
+    crate::session::require(&app, &user_id)?;
     crate::watcher::stop_watching(&app, &user_id)
 }
 
@@ -458,18 +1524,71 @@ pub struct MediaWithMetadata {
     pub backdrop_path: Option<String>,
     /// Absolute file path to the episode still image (TV episodes)
     pub still_path: Option<String>,
+    /// Every file of the title when it exists in several versions, this one included
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<crate::versions::MediaVersion>,
+    /// Trailers, featurettes and other extras found with the title
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extras: Vec<crate::extras::MediaExtra>,
+}
+
+#[cfg(test)]
+impl MediaWithMetadata {
+    /// A library item at `path`, without metadata, with "id:<path>" as ID
+    pub fn test(path: &str) -> Self {
+        serde_json::from_value(serde_json::json!({
+            "id": format!("id:{}", path),
+            "path": path,
+            "filename": Path::new(path).file_name().and_then(|n| n.to_str()),
+            "extension": Path::new(path).extension().and_then(|e| e.to_str()),
+            "size_bytes": 1,
+            "metadata": null,
+            "poster_path": null,
+            "backdrop_path": null,
+            "still_path": null,
+        }))
+        .unwrap()
+    }
+
+    pub fn with_size(mut self, size_bytes: u64) -> Self {
+        self.entry.size_bytes = size_bytes;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
 }
 
 /// Get the full media library with metadata and resolved image paths in a single call
 #[tauri::command]
 pub fn get_library_with_metadata(app: AppHandle, user_id: String) -> Result<Vec<MediaWithMetadata>, String> {
-    let entries = get_media_library(app.clone(), user_id.clone())?;
+    crate::session::require(&app, &user_id)?;
+    let results = library_with_metadata(&app, &user_id)?;
+
+    log::info!(
+        "[Media] Loaded {} entries with metadata for user {}",
+        results.len(),
+        user_id
+    );
+    Ok(results)
+}
+
+/// The library as shown: entries with their metadata and images, versions of a
+/// title grouped and extras attached to their parent
+pub fn library_with_metadata(
+    app: &AppHandle,
+    user_id: &str,
+) -> Result<Vec<MediaWithMetadata>, String> {
+    let entries = load_library(app, user_id)?;
+    let mut metadata = crate::db::load_all_metadata(app, user_id)?;
 
     let results: Vec<MediaWithMetadata> = entries
         .into_iter()
         .map(|entry| {
-            let meta = crate::metadata::get_metadata(&app, &user_id, &entry.id).ok();
-            let meta_dir = get_user_dir(&app, &user_id)
+            let meta = metadata.remove(&entry.id);
+            let meta_dir = get_user_dir(app, user_id)
                 .map(|d| d.join("metas").join(&entry.id))
                 .ok();
 
@@ -498,21 +1617,21 @@ pub fn get_library_with_metadata(app: AppHandle, user_id: String) -> Result<Vec<
                 poster_path,
                 backdrop_path,
                 still_path,
+                versions: Vec::new(),
+                extras: Vec::new(),
             }
         })
         .collect();
 
-    log::info!(
-        "[Media] Loaded {} entries with metadata for user {}",
-        results.len(),
-        user_id
-    );
-    Ok(results)
+    let settings = load_settings(app, user_id).unwrap_or_default();
+    let results = crate::versions::group_versions(app, user_id, results, settings.default_version);
+    Ok(crate::extras::attach_extras(results, &settings.media))
 }
 
 /// Delete log files older than the configured retention period
 #[tauri::command]
 pub fn cleanup_old_logs(app: AppHandle, user_id: String) -> Result<u32, String> {
+    crate::session::require(&app, &user_id)?;
     let settings = load_settings(&app, &user_id)?;
     let retention_months = settings.log_retention_months.max(1).min(12);
 
@@ -561,3 +1680,212 @@ pub fn cleanup_old_logs(app: AppHandle, user_id: String) -> Result<u32, String>
     );
     Ok(deleted)
 }
+
+/// Helpers shared by the tests of the modules working on library files
+#[cfg(test)]
+pub mod testing {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// A directory in the temp folder, deleted when dropped
+    pub struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("popcorn-test-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+
+        /// Write a file at `path` (relative to the directory), creating its parents
+        pub fn write(&self, path: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+
+    fn entry(path: &str, size: u64) -> MediaEntry {
+        MediaEntry {
+            size_bytes: size,
+            ..entry_with_id(Path::new(path), path.to_string())
+        }
+    }
+
+    #[test]
+    fn recognizes_part_markers() {
+        for name in [
+            "Movie.2001.CD1.avi",
+            "Movie - part2.mkv",
+            "Movie_disc 1.mkv",
+            "Movie.pt.2.mp4",
+        ] {
+            assert!(is_stack_part(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn ignores_markers_inside_words() {
+        for name in [
+            "Concept 1.mkv",
+            "Counterpart 2.mkv",
+            "Abcd1.mkv",
+            "Movie.DVDRip.mkv",
+        ] {
+            assert!(!is_stack_part(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn stacks_sequential_parts_of_the_same_title() {
+        let entries = vec![
+            entry("/movies/Movie.2001.CD2.avi", 200),
+            entry("/movies/Movie.2001.CD1.avi", 100),
+            entry("/movies/Other.avi", 50),
+        ];
+        let mut stacked = stack_parts(entries);
+        stacked.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(stacked.len(), 2);
+        let movie = &stacked[0];
+        assert_eq!(movie.path, "/movies/Movie.2001.CD1.avi");
+        assert_eq!(movie.filename, "Movie.2001.avi");
+        assert_eq!(movie.size_bytes, 300);
+        assert_eq!(
+            movie.parts,
+            vec!["/movies/Movie.2001.CD1.avi", "/movies/Movie.2001.CD2.avi"]
+        );
+        assert!(stacked[1].parts.is_empty());
+    }
+
+    #[test]
+    fn leaves_incomplete_stacks_apart() {
+        let entries = vec![
+            entry("/movies/Movie.CD1.avi", 100),
+            entry("/movies/Movie.CD3.avi", 100),
+            entry("/other/Movie.CD2.avi", 100),
+        ];
+        let stacked = stack_parts(entries);
+        assert_eq!(stacked.len(), 3);
+        assert!(stacked.iter().all(|e| e.parts.is_empty()));
+    }
+
+    #[test]
+    fn history_follows_the_path_and_the_content() {
+        let dated = |path: &str, id: &str, added_at: i64, last_seen_at: i64| MediaEntry {
+            added_at,
+            last_seen_at,
+            ..entry_with_id(Path::new(path), id.to_string())
+        };
+        let fresh = entry_with_id(Path::new("/movies/Renamed.mkv"), "a".to_string());
+
+        // Renamed: found by ID only
+        let moved = dated("/movies/Old.mkv", "a", 100, 300);
+        let entry = with_history(fresh.clone(), [&moved]);
+        assert_eq!((entry.added_at, entry.last_seen_at), (100, 300));
+
+        // Rewritten over another file: the earliest date wins
+        let replaced = dated("/movies/Renamed.mkv", "b", 50, 200);
+        let entry = with_history(fresh.clone(), [&replaced, &moved]);
+        assert_eq!((entry.added_at, entry.last_seen_at), (50, 300));
+
+        let entry = with_history(fresh.clone(), []);
+        assert_eq!(entry.added_at, fresh.added_at);
+    }
+
+    #[test]
+    fn an_emptied_folder_that_held_media_is_offline() {
+        let dir = TempDir::new();
+        let root = dir.path().join("Movies");
+        fs::create_dir(&root).unwrap();
+        let folder = MediaFolder::new(root.to_string_lossy().to_string());
+        let config = MediaConfig {
+            folders: vec![folder.clone(), MediaFolder::new("/other".to_string())],
+            default_exclusions: true,
+        };
+        let held = entry(&root.join("Heat.mkv").to_string_lossy(), 1);
+        let elsewhere = entry("/other/Heat.mkv", 1);
+
+        // Empty without having held anything: a new folder, or one emptied on purpose
+        assert!(is_folder_available(&config, &folder, [&elsewhere]));
+        // Empty after holding media: the drive mounted there is gone
+        assert!(!is_folder_available(&config, &folder, [&held, &elsewhere]));
+
+        dir.write("Movies/Heat.mkv", "");
+        assert!(is_folder_available(&config, &folder, [&held]));
+
+        fs::remove_dir_all(&root).unwrap();
+        assert!(!is_folder_available(&config, &folder, []));
+    }
+
+    /// Scan `root` after a scan that left `cache` and `entries`, returning the
+    /// new ones and how many directories came from the cache and files were hashed
+    fn scan(
+        root: &Path,
+        cache: &ScanCache,
+        entries: &[MediaEntry],
+    ) -> (ScanCache, Vec<MediaEntry>, usize, usize) {
+        let previous: HashMap<String, MediaEntry> = entries
+            .iter()
+            .map(|e| (e.path.clone(), e.clone()))
+            .collect();
+        let mut ctx = ScanContext {
+            previous_cache: cache,
+            previous_entries: &previous,
+            previous_ids: previous.values().map(|e| (e.id.as_str(), e)).collect(),
+            cache: ScanCache::default(),
+            entries: Vec::new(),
+            cached_dirs: 0,
+            hashed_files: 0,
+            ignored: 0,
+        };
+        let folder = MediaFolder::new(root.to_string_lossy().to_string());
+        scan_directory(root, &IgnoreRules::for_folder(&folder, true), &mut ctx);
+        (ctx.cache, ctx.entries, ctx.cached_dirs, ctx.hashed_files)
+    }
+
+    #[test]
+    fn files_overwritten_in_an_unchanged_directory_are_hashed_again() {
+        let dir = TempDir::new();
+        let movie = dir.write("Heat.mkv", "first cut");
+        dir.write("Ronin.mkv", "ronin");
+        let (cache, entries, _, hashed) = scan(dir.path(), &ScanCache::default(), &[]);
+        assert_eq!(hashed, 2);
+
+        // Unchanged: the listing and the fingerprints come from the cache
+        let (_, again, cached_dirs, hashed) = scan(dir.path(), &cache, &entries);
+        assert_eq!((cached_dirs, hashed), (1, 0));
+        assert_eq!(again.len(), 2);
+
+        // Rewritten in place: the directory is untouched, the file is not
+        fs::write(&movie, "director's cut").unwrap();
+        let (_, rescanned, cached_dirs, hashed) = scan(dir.path(), &cache, &entries);
+        assert_eq!((cached_dirs, hashed), (1, 1));
+        let id_of = |entries: &[MediaEntry]| {
+            entries
+                .iter()
+                .find(|e| e.filename == "Heat.mkv")
+                .map(|e| (e.id.clone(), e.size_bytes))
+                .unwrap()
+        };
+        assert_ne!(id_of(&rescanned).0, id_of(&entries).0);
+        assert_eq!(id_of(&rescanned).1, 14);
+    }
+}
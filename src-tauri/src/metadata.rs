@@ -1,12 +1,13 @@
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
 
 use crate::media;
 
-// ─── Standard metadata structure (stored as metas/<uuid>/meta.json) ─────────
+// ─── Standard metadata structure (stored in the library database) ───────────
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct VideoMetadata {
@@ -121,13 +122,33 @@ struct ParsedFilename {
 ///   "Movie_Name_2024_720p.mkv"
 ///   "Series.Name.S01E05.1080p.WEB-DL.mkv"
 ///   "Series.Name.S02E10.CUSTOM.MULTi.1080p.mkv"
-fn parse_filename(filename: &str) -> ParsedFilename {
+///   "Series Name 2x05.mkv" (series folders only)
+///
+/// The folder's library type decides between movie and episode: only mixed
+/// folders guess it from the filename.
+fn parse_filename(filename: &str, library_type: media::LibraryType) -> ParsedFilename {
     // Remove extension
     let name = filename
         .rsplit_once('.')
         .map(|(name, _ext)| name)
         .unwrap_or(filename);
 
+    parse_name(name, library_type)
+}
+
+/// Parse the name of a media entry: Blu-ray/DVD folders are titled after the
+/// folder, whose name has no extension to strip
+fn parse_entry(entry: &media::MediaEntry, library_type: media::LibraryType) -> ParsedFilename {
+    match &entry.disc {
+        Some(disc) if disc.format != crate::disc::DiscFormat::Iso => {
+            parse_name(&entry.filename, library_type)
+        }
+        _ => parse_filename(&entry.filename, library_type),
+    }
+}
+
+/// Parse a filename without its extension, see `parse_filename`
+fn parse_name(name: &str, library_type: media::LibraryType) -> ParsedFilename {
     // ─── Detect TV series pattern (S01E02, S1E5, etc.) ──────────────
     let tv_re = Regex::new(r"(?i)[\.\s_\-]S(\d{1,2})E(\d{1,3})").unwrap();
     let mut is_tv = false;
@@ -135,11 +156,24 @@ fn parse_filename(filename: &str) -> ParsedFilename {
     let mut episode: Option<u32> = None;
     let mut tv_title_end: Option<usize> = None;
 
-    if let Some(caps) = tv_re.captures(name) {
+    if library_type != media::LibraryType::Movies {
+        if let Some(caps) = tv_re.captures(name) {
+            is_tv = true;
+            season = caps[1].parse().ok();
+            episode = caps[2].parse().ok();
+            tv_title_end = caps.get(0).map(|m| m.start());
+        }
+    }
+
+    // Series folders also use "2x05" numbering, and everything in them is an episode
+    if library_type == media::LibraryType::Series && !is_tv {
+        let alt_re = Regex::new(r"(?i)[\.\s_\-](\d{1,2})x(\d{2,3})(?:[\.\s_\-]|$)").unwrap();
+        if let Some(caps) = alt_re.captures(name) {
+            season = caps[1].parse().ok();
+            episode = caps[2].parse().ok();
+            tv_title_end = caps.get(0).map(|m| m.start());
+        }
         is_tv = true;
-        season = caps[1].parse().ok();
-        episode = caps[2].parse().ok();
-        tv_title_end = caps.get(0).map(|m| m.start());
     }
 
     // If it's a TV show, extract title from before the S01E02 pattern
@@ -261,8 +295,21 @@ const TMDB_DEFAULT_API_KEY: &str = match option_env!("TMDB_API_KEY") {
 
 /// Build a minimal VideoMetadata from filename parsing and file-level info.
 /// This is used when no API provider is available or all providers fail.
-fn build_local_metadata(entry: &media::MediaEntry) -> VideoMetadata {
-    let parsed = parse_filename(&entry.filename);
+fn build_local_metadata(
+    entry: &media::MediaEntry,
+    library_type: media::LibraryType,
+) -> VideoMetadata {
+    let mut parsed = parse_entry(entry, library_type);
+    if entry.extra.is_some() {
+        // Extras are named after what they show, not after a release
+        parsed = ParsedFilename {
+            title: crate::extras::extra_title(std::path::Path::new(&entry.path)),
+            year: None,
+            season: None,
+            episode: None,
+            is_tv: false,
+        };
+    }
     VideoMetadata {
         title: parsed.title,
         original_title: None,
@@ -292,7 +339,13 @@ fn build_local_metadata(entry: &media::MediaEntry) -> VideoMetadata {
         file_path: Some(entry.path.clone()),
         provider: "local".to_string(),
         fetched_at: chrono::Local::now().to_rfc3339(),
-        media_type: if parsed.is_tv { "tv".to_string() } else { "unknown".to_string() },
+        media_type: match library_type {
+            _ if entry.extra.is_some() => "extra".to_string(),
+            media::LibraryType::HomeVideos => "home_video".to_string(),
+            media::LibraryType::Movies => "movie".to_string(),
+            _ if parsed.is_tv => "tv".to_string(),
+            _ => "unknown".to_string(),
+        },
     }
 }
 
@@ -301,6 +354,23 @@ fn build_local_metadata(entry: &media::MediaEntry) -> VideoMetadata {
 const TMDB_BASE_URL: &str = "https://api.themoviedb.org/3";
 const TMDB_IMAGE_BASE: &str = "https://image.tmdb.org/t/p";
 
+/// Credentials and request options shared by every TMDB call
+struct TmdbOptions<'a> {
+    api_key: &'a str,
+    /// Language of titles and overviews (e.g. "fr-FR"), TMDB default if unset
+    language: Option<&'a str>,
+}
+
+impl TmdbOptions<'_> {
+    /// Query string parameters for a TMDB request
+    fn query(&self) -> String {
+        match self.language {
+            Some(language) => format!("api_key={}&language={}", self.api_key, urlencoded(language)),
+            None => format!("api_key={}", self.api_key),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 struct TmdbSearchResult {
     results: Vec<TmdbMovie>,
@@ -382,15 +452,15 @@ struct TmdbFetchResult {
 
 fn fetch_from_tmdb(
     client: &reqwest::blocking::Client,
-    api_key: &str,
+    tmdb: &TmdbOptions,
     title: &str,
     year: Option<u32>,
 ) -> Result<Option<TmdbFetchResult>, String> {
     // Search for the movie
     let mut url = format!(
-        "{}/search/movie?api_key={}&query={}",
+        "{}/search/movie?{}&query={}",
         TMDB_BASE_URL,
-        api_key,
+        tmdb.query(),
         urlencoded(title)
     );
     if let Some(y) = year {
@@ -422,8 +492,10 @@ fn fetch_from_tmdb(
 
     // Get detailed info with credits
     let detail_url = format!(
-        "{}/movie/{}?api_key={}&append_to_response=credits",
-        TMDB_BASE_URL, movie.id, api_key
+        "{}/movie/{}?{}&append_to_response=credits",
+        TMDB_BASE_URL,
+        movie.id,
+        tmdb.query()
     );
 
     let detail_resp = client
@@ -629,14 +701,18 @@ struct TmdbEpisodeDetail {
 /// Fetch episode-specific details from TMDB
 fn fetch_episode_from_tmdb(
     client: &reqwest::blocking::Client,
-    api_key: &str,
+    tmdb: &TmdbOptions,
     series_id: u64,
     season: u32,
     episode: u32,
 ) -> Result<Option<TmdbEpisodeDetail>, String> {
     let url = format!(
-        "{}/tv/{}/season/{}/episode/{}?api_key={}",
-        TMDB_BASE_URL, series_id, season, episode, api_key
+        "{}/tv/{}/season/{}/episode/{}?{}",
+        TMDB_BASE_URL,
+        series_id,
+        season,
+        episode,
+        tmdb.query()
     );
 
     log::info!(
@@ -662,16 +738,16 @@ fn fetch_episode_from_tmdb(
 
 fn fetch_tv_from_tmdb(
     client: &reqwest::blocking::Client,
-    api_key: &str,
+    tmdb: &TmdbOptions,
     title: &str,
     season: Option<u32>,
     episode: Option<u32>,
 ) -> Result<Option<TmdbFetchResult>, String> {
     // Search for the TV show
     let url = format!(
-        "{}/search/tv?api_key={}&query={}",
+        "{}/search/tv?{}&query={}",
         TMDB_BASE_URL,
-        api_key,
+        tmdb.query(),
         urlencoded(title)
     );
 
@@ -700,8 +776,10 @@ fn fetch_tv_from_tmdb(
 
     // Get detailed info with credits
     let detail_url = format!(
-        "{}/tv/{}?api_key={}&append_to_response=credits",
-        TMDB_BASE_URL, show.id, api_key
+        "{}/tv/{}?{}&append_to_response=credits",
+        TMDB_BASE_URL,
+        show.id,
+        tmdb.query()
     );
 
     let detail_resp = client
@@ -783,7 +861,7 @@ fn fetch_tv_from_tmdb(
     let mut ep_episode = None;
 
     if let (Some(s), Some(e)) = (season, episode) {
-        match fetch_episode_from_tmdb(client, api_key, detail.id, s, e) {
+        match fetch_episode_from_tmdb(client, tmdb, detail.id, s, e) {
             Ok(Some(ep)) => {
                 log::info!(
                     "[Metadata/TMDB] Episode S{:02}E{:02}: \"{}\"",
@@ -891,16 +969,19 @@ struct OmdbFetchResult {
     poster_url: Option<String>,
 }
 
+/// Search OMDb for a movie, or for a series when `series` is set
 fn fetch_from_omdb(
     client: &reqwest::blocking::Client,
     api_key: &str,
     title: &str,
     year: Option<u32>,
+    series: bool,
 ) -> Result<Option<OmdbFetchResult>, String> {
     let mut url = format!(
-        "https://www.omdbapi.com/?apikey={}&t={}&type=movie&plot=full",
+        "https://www.omdbapi.com/?apikey={}&t={}&type={}&plot=full",
         api_key,
-        urlencoded(title)
+        urlencoded(title),
+        if series { "series" } else { "movie" }
     );
     if let Some(y) = year {
         url.push_str(&format!("&y={}", y));
@@ -1022,7 +1103,7 @@ fn fetch_from_omdb(
         file_path: None,
         provider: "omdb".to_string(),
         fetched_at: chrono::Local::now().to_rfc3339(),
-        media_type: "movie".to_string(), // OMDb only searches movies
+        media_type: if series { "tv" } else { "movie" }.to_string(),
     };
 
     Ok(Some(OmdbFetchResult {
@@ -1102,18 +1183,20 @@ struct TmdbMultiItem {
 /// Use TMDB multi-search as a fallback. This searches movies, TV, and people
 /// simultaneously — useful when we don't know if it's a movie or TV show,
 /// or when the dedicated search returned no results.
+/// `only` restricts the results to "movie" or "tv" (movies and series folders).
 fn fetch_via_multi_search(
     client: &reqwest::blocking::Client,
-    api_key: &str,
+    tmdb: &TmdbOptions,
     title: &str,
     year: Option<u32>,
     season: Option<u32>,
     episode: Option<u32>,
+    only: Option<&str>,
 ) -> Result<Option<TmdbFetchResult>, String> {
     let mut url = format!(
-        "{}/search/multi?api_key={}&query={}",
+        "{}/search/multi?{}&query={}",
         TMDB_BASE_URL,
-        api_key,
+        tmdb.query(),
         urlencoded(title)
     );
     if let Some(y) = year {
@@ -1137,16 +1220,19 @@ fn fetch_via_multi_search(
 
     // Find the first movie or TV result
     for item in &search.results {
+        if only.is_some_and(|only| only != item.media_type) {
+            continue;
+        }
         match item.media_type.as_str() {
             "movie" => {
                 log::info!("[Metadata/TMDB] Multi-search found movie id={}", item.id);
                 // Re-use the movie detail fetch
-                return fetch_movie_by_id(client, api_key, item.id);
+                return fetch_movie_by_id(client, tmdb, item.id);
             }
             "tv" => {
                 log::info!("[Metadata/TMDB] Multi-search found TV show id={}", item.id);
                 // Re-use the TV detail fetch
-                return fetch_tv_by_id(client, api_key, item.id, season, episode);
+                return fetch_tv_by_id(client, tmdb, item.id, season, episode);
             }
             _ => continue,
         }
@@ -1159,12 +1245,14 @@ fn fetch_via_multi_search(
 /// Fetch a movie by TMDB ID directly (used by multi-search fallback)
 fn fetch_movie_by_id(
     client: &reqwest::blocking::Client,
-    api_key: &str,
+    tmdb: &TmdbOptions,
     movie_id: u64,
 ) -> Result<Option<TmdbFetchResult>, String> {
     let detail_url = format!(
-        "{}/movie/{}?api_key={}&append_to_response=credits",
-        TMDB_BASE_URL, movie_id, api_key
+        "{}/movie/{}?{}&append_to_response=credits",
+        TMDB_BASE_URL,
+        movie_id,
+        tmdb.query()
     );
 
     let detail_resp = client
@@ -1260,14 +1348,16 @@ fn fetch_movie_by_id(
 /// Fetch a TV show by TMDB ID directly (used by multi-search fallback)
 fn fetch_tv_by_id(
     client: &reqwest::blocking::Client,
-    api_key: &str,
+    tmdb: &TmdbOptions,
     tv_id: u64,
     season: Option<u32>,
     episode: Option<u32>,
 ) -> Result<Option<TmdbFetchResult>, String> {
     let detail_url = format!(
-        "{}/tv/{}?api_key={}&append_to_response=credits",
-        TMDB_BASE_URL, tv_id, api_key
+        "{}/tv/{}?{}&append_to_response=credits",
+        TMDB_BASE_URL,
+        tv_id,
+        tmdb.query()
     );
 
     let detail_resp = client
@@ -1334,7 +1424,7 @@ fn fetch_tv_by_id(
     let mut ep_episode = None;
 
     if let (Some(s), Some(e)) = (season, episode) {
-        match fetch_episode_from_tmdb(client, api_key, tv_id, s, e) {
+        match fetch_episode_from_tmdb(client, tmdb, tv_id, s, e) {
             Ok(Some(ep)) => {
                 episode_title = ep.name;
                 episode_overview = ep.overview;
@@ -1388,60 +1478,56 @@ fn fetch_tv_by_id(
 
 // ─── Metas folder management ────────────────────────────────────────────────
 
-/// Get the metas directory path for a user
+/// Get the metas directory path for a user (images, by media ID)
 fn get_metas_dir(app: &AppHandle, user_id: &str) -> Result<PathBuf, String> {
     let user_dir = media::get_user_dir_public(app, user_id)?;
     Ok(user_dir.join("metas"))
 }
 
-/// Get the metadata directory for a specific media entry
+/// Get the image directory for a specific media entry
 fn get_meta_dir(app: &AppHandle, user_id: &str, media_id: &str) -> Result<PathBuf, String> {
     Ok(get_metas_dir(app, user_id)?.join(media_id))
 }
 
 /// Check if metadata exists for a given media entry.
-/// Returns true only if a meta.json exists AND was fetched from a real API provider
+/// Returns true only if metadata exists AND was fetched from a real API provider
 /// AND is not older than `cache_months` months.
 /// Local-only metadata (from filename parsing) is treated as "missing" so it gets
 /// re-fetched when an API provider becomes available.
 fn has_rich_metadata(app: &AppHandle, user_id: &str, media_id: &str, cache_months: u32) -> bool {
-    if let Ok(dir) = get_meta_dir(app, user_id, media_id) {
-        let meta_path = dir.join("meta.json");
-        if !meta_path.exists() {
-            return false;
-        }
-        // Check if this was fetched from a real API provider (not just local)
-        if let Ok(raw) = fs::read_to_string(&meta_path) {
-            if let Ok(meta) = serde_json::from_str::<serde_json::Value>(&raw) {
-                let provider = meta.get("provider").and_then(|p| p.as_str()).unwrap_or("local");
-                if provider == "local" {
-                    return false;
-                }
-                // Check if cached metadata is still fresh
-                if let Some(fetched_at) = meta.get("fetched_at").and_then(|f| f.as_str()) {
-                    if let Ok(fetched_time) = chrono::DateTime::parse_from_rfc3339(fetched_at) {
-                        let now = chrono::Local::now();
-                        let age = now.signed_duration_since(fetched_time);
-                        let max_age = chrono::Duration::days(cache_months as i64 * 30);
-                        if age > max_age {
-                            log::info!(
-                                "[Metadata] Cache expired for {} (age: {} days, max: {} days)",
-                                media_id,
-                                age.num_days(),
-                                max_age.num_days()
-                            );
-                            return false;
-                        }
-                    }
-                }
-                return true;
+    let meta = match crate::db::load_metadata(app, user_id, media_id) {
+        Ok(Some(meta)) => meta,
+        Ok(None) => return false,
+        // If we can't determine the provider, treat as existing
+        Err(_) => return true,
+    };
+
+    // Check if this was fetched from a real API provider (not just local)
+    let provider = meta
+        .get("provider")
+        .and_then(|p| p.as_str())
+        .unwrap_or("local");
+    if provider == "local" {
+        return false;
+    }
+    // Check if cached metadata is still fresh
+    if let Some(fetched_at) = meta.get("fetched_at").and_then(|f| f.as_str()) {
+        if let Ok(fetched_time) = chrono::DateTime::parse_from_rfc3339(fetched_at) {
+            let now = chrono::Local::now();
+            let age = now.signed_duration_since(fetched_time);
+            let max_age = chrono::Duration::days(cache_months as i64 * 30);
+            if age > max_age {
+                log::info!(
+                    "[Metadata] Cache expired for {} (age: {} days, max: {} days)",
+                    media_id,
+                    age.num_days(),
+                    max_age.num_days()
+                );
+                return false;
             }
         }
-        // If we can't determine the provider, treat as existing
-        true
-    } else {
-        false
     }
+    true
 }
 
 /// Get metadata for a specific media entry
@@ -1450,16 +1536,78 @@ pub fn get_metadata(
     user_id: &str,
     media_id: &str,
 ) -> Result<serde_json::Value, String> {
-    let meta_dir = get_meta_dir(app, user_id, media_id)?;
-    let meta_path = meta_dir.join("meta.json");
+    crate::db::load_metadata(app, user_id, media_id)?
+        .ok_or_else(|| "Metadata not found".to_string())
+}
+
+/// Keep the metadata of an entry attached after its ID or path changed.
+/// `previous_id` is the ID the entry had at this path before (content modified in
+/// place, or a library from before content-based IDs); its metadata and images
+/// are moved over when the new ID has none. The stored file path follows the entry.
+pub fn reattach_metadata(
+    app: &AppHandle,
+    user_id: &str,
+    previous_id: Option<&str>,
+    entry: &media::MediaEntry,
+) -> Result<(), String> {
+    if let Some(previous_id) = previous_id.filter(|id| *id != entry.id) {
+        let meta_dir = get_meta_dir(app, user_id, &entry.id)?;
+        let previous_dir = get_meta_dir(app, user_id, previous_id)?;
+        let moved = crate::db::move_metadata(app, user_id, previous_id, &entry.id)?;
+        if moved && !meta_dir.exists() && previous_dir.exists() {
+            fs::rename(&previous_dir, &meta_dir).map_err(|e| e.to_string())?;
+        }
+        if moved {
+            log::info!(
+                "[Metadata] Moved metadata of {} from {} to {}",
+                entry.filename,
+                previous_id,
+                entry.id
+            );
+        }
+    }
 
-    if !meta_path.exists() {
-        return Err("Metadata not found".to_string());
+    let mut meta = match crate::db::load_metadata(app, user_id, &entry.id)? {
+        Some(meta) => meta,
+        None => return Ok(()),
+    };
+    if meta.get("file_path").and_then(|p| p.as_str()) == Some(entry.path.as_str()) {
+        return Ok(());
     }
 
-    let raw = fs::read_to_string(&meta_path).map_err(|e| e.to_string())?;
-    let meta: serde_json::Value = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
-    Ok(meta)
+    meta["file_path"] = serde_json::Value::String(entry.path.clone());
+    crate::db::save_metadata(app, user_id, &entry.id, &meta)?;
+    log::info!(
+        "[Metadata] Reattached metadata {} to {}",
+        entry.id,
+        entry.path
+    );
+    Ok(())
+}
+
+/// Drop the stored metadata of entries so the next fetch resolves them again
+/// (after their folder's library type or language changed)
+pub fn invalidate_metadata(app: &AppHandle, user_id: &str, entries: &[media::MediaEntry]) {
+    let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+    let removed = match crate::db::delete_metadata(app, user_id, &ids) {
+        Ok(removed) => removed,
+        Err(e) => {
+            log::warn!("[Metadata] Failed to invalidate metadata: {}", e);
+            0
+        }
+    };
+    for entry in entries {
+        if let Ok(dir) = get_meta_dir(app, user_id, &entry.id) {
+            if dir.exists() {
+                let _ = fs::remove_dir_all(&dir);
+            }
+        }
+    }
+    log::info!(
+        "[Metadata] Invalidated metadata of {} entr(ies) for user {}",
+        removed,
+        user_id
+    );
 }
 
 /// Save metadata and download images for a media entry
@@ -1535,11 +1683,11 @@ fn save_metadata_and_images(
         }
     }
 
-    // Save meta.json
-    let meta_json =
-        serde_json::to_string_pretty(metadata).map_err(|e| format!("Failed to serialize: {}", e))?;
-    let meta_path = meta_dir.join("meta.json");
-    fs::write(&meta_path, meta_json).map_err(|e| format!("Failed to write meta.json: {}", e))?;
+    // Save metadata
+    let meta =
+        serde_json::to_value(&*metadata).map_err(|e| format!("Failed to serialize: {}", e))?;
+    crate::db::save_metadata(app, user_id, media_id, &meta)
+        .map_err(|e| format!("Failed to save metadata: {}", e))?;
 
     log::info!(
         "[Metadata] Metadata saved for {} (provider: {})",
@@ -1549,6 +1697,140 @@ fn save_metadata_and_images(
     Ok(())
 }
 
+// ─── Garbage collection ─────────────────────────────────────────────────────
+
+/// Result of a metadata garbage collection pass
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MetadataGcResult {
+    /// Metadata (with its images) deleted because its entry left the library
+    pub removed: usize,
+    /// Metadata of missing entries kept until their grace period ends
+    pub pending: usize,
+    pub reclaimed_bytes: u64,
+    /// Size of the image cache (metas folder) after the pass
+    pub cache_bytes: u64,
+}
+
+/// Total size of the files of a metas folder
+fn dir_size(dir: &std::path::Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|rd| {
+            rd.flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Delete the metadata of entries no longer in the library, once they have been
+/// missing for the grace period (a drive may only be unplugged), then shrink the
+/// image cache towards its maximum size by deleting orphans still in their grace
+/// period, the longest missing first. The metadata and images of library entries
+/// are always kept: deleting them would leave titles without art that is never
+/// fetched again.
+pub fn collect_garbage(app: &AppHandle, user_id: &str) -> Result<MetadataGcResult, String> {
+    // Started after a scan: the user may have been locked out meanwhile
+    if !crate::session::is_active(app, user_id) {
+        return Err("Session ended".to_string());
+    }
+    let settings = media::load_settings(app, user_id)?;
+    let library: HashSet<String> = media::load_library(app, user_id)?
+        .into_iter()
+        .map(|e| e.id)
+        .collect();
+    let metas_dir = get_metas_dir(app, user_id)?;
+    let mut result = MetadataGcResult::default();
+
+    // Media IDs with metadata or images
+    let mut ids: HashSet<String> = crate::db::metadata_ids(app, user_id)?.into_iter().collect();
+    if let Ok(entries) = fs::read_dir(&metas_dir) {
+        ids.extend(
+            entries
+                .flatten()
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().to_string()),
+        );
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let grace_secs = settings.metadata_gc_grace_days as i64 * 24 * 3600;
+    let previous_orphans = crate::db::load_orphans(app, user_id)?;
+    let mut orphans: HashMap<String, i64> = HashMap::new();
+    // Metas folders still there after the first pass: (path, orphaned since)
+    let mut kept: Vec<(PathBuf, Option<i64>)> = Vec::new();
+    // Orphans whose metadata goes
+    let mut deleted: Vec<String> = Vec::new();
+
+    for id in ids {
+        let dir = metas_dir.join(&id);
+        if library.contains(&id) {
+            kept.push((dir, None));
+            continue;
+        }
+
+        let since = previous_orphans.get(&id).copied().unwrap_or(now);
+        if now - since < grace_secs {
+            orphans.insert(id, since);
+            kept.push((dir, Some(since)));
+            continue;
+        }
+        let size = dir_size(&dir);
+        if dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                log::warn!(
+                    "[Metadata] Failed to delete orphaned metadata {}: {}",
+                    id,
+                    e
+                );
+                continue;
+            }
+        }
+        result.removed += 1;
+        result.reclaimed_bytes += size;
+        deleted.push(id);
+    }
+
+    result.cache_bytes = kept.iter().map(|(dir, _)| dir_size(dir)).sum();
+    let max_bytes = settings.metadata_cache_max_mb as u64 * 1024 * 1024;
+    if max_bytes > 0 && result.cache_bytes > max_bytes {
+        // Orphaned folders, the longest missing first
+        let mut orphan_dirs: Vec<&(PathBuf, Option<i64>)> =
+            kept.iter().filter(|(_, since)| since.is_some()).collect();
+        orphan_dirs.sort_by_key(|(_, since)| *since);
+        for (dir, _) in orphan_dirs {
+            if result.cache_bytes <= max_bytes {
+                break;
+            }
+            let size = dir_size(dir);
+            if fs::remove_dir_all(dir).is_ok() {
+                result.removed += 1;
+                result.reclaimed_bytes += size;
+                result.cache_bytes = result.cache_bytes.saturating_sub(size);
+                if let Some(id) = dir.file_name().and_then(|n| n.to_str()) {
+                    orphans.remove(id);
+                    deleted.push(id.to_string());
+                }
+            }
+        }
+    }
+
+    let deleted: Vec<&str> = deleted.iter().map(String::as_str).collect();
+    crate::db::delete_metadata(app, user_id, &deleted)?;
+    result.pending = orphans.len();
+    crate::db::save_orphans(app, user_id, &orphans)?;
+    log::info!(
+        "[Metadata] Garbage collection for user {}: {} removed, {} pending, {} bytes reclaimed, cache now {} bytes",
+        user_id,
+        result.removed,
+        result.pending,
+        result.reclaimed_bytes,
+        result.cache_bytes
+    );
+    Ok(result)
+}
+
 // ─── Main fetch orchestration ───────────────────────────────────────────────
 
 /// Fetch metadata for a single media entry, trying providers in order.
@@ -1556,17 +1838,25 @@ fn save_metadata_and_images(
 /// metadata extracted from the filename and file info.
 ///
 /// Search strategy for TMDB:
-/// 1. Dedicated search (movie or TV based on filename pattern)
+/// 1. Dedicated search (movie or TV based on the folder type or filename pattern)
 /// 2. Multi-search fallback with parsed title
 /// 3. Multi-search with progressively simplified title (remove trailing words)
+///
+/// Fallback searches of movies and series folders only accept that kind of result.
 fn fetch_metadata_for_entry(
     client: &reqwest::blocking::Client,
     app: &AppHandle,
     user_id: &str,
     entry: &media::MediaEntry,
+    folder: &media::MediaFolder,
     providers: &[media::MetadataProviderConfig],
 ) -> Result<bool, String> {
-    let parsed = parse_filename(&entry.filename);
+    let parsed = parse_entry(entry, folder.library_type);
+    let only = match folder.library_type {
+        media::LibraryType::Movies => Some("movie"),
+        media::LibraryType::Series => Some("tv"),
+        _ => None,
+    };
     log::info!(
         "[Metadata] Parsed filename \"{}\": title=\"{}\", year={:?}, is_tv={}, season={:?}, episode={:?}",
         entry.filename,
@@ -1591,12 +1881,16 @@ fn fetch_metadata_for_entry(
                 } else {
                     provider.api_key.clone()
                 };
+                let tmdb = TmdbOptions {
+                    api_key: &api_key,
+                    language: folder.metadata_language.as_deref(),
+                };
 
                 // Strategy 1: Dedicated search (movie or TV based on filename pattern)
                 let tmdb_result = if parsed.is_tv {
-                    fetch_tv_from_tmdb(client, &api_key, &parsed.title, parsed.season, parsed.episode)
+                    fetch_tv_from_tmdb(client, &tmdb, &parsed.title, parsed.season, parsed.episode)
                 } else {
-                    fetch_from_tmdb(client, &api_key, &parsed.title, parsed.year)
+                    fetch_from_tmdb(client, &tmdb, &parsed.title, parsed.year)
                 };
 
                 if let Ok(Some(result)) = tmdb_result {
@@ -1617,7 +1911,13 @@ fn fetch_metadata_for_entry(
                     parsed.title
                 );
                 if let Ok(Some(result)) = fetch_via_multi_search(
-                    client, &api_key, &parsed.title, parsed.year, parsed.season, parsed.episode,
+                    client,
+                    &tmdb,
+                    &parsed.title,
+                    parsed.year,
+                    parsed.season,
+                    parsed.episode,
+                    only,
                 ) {
                     let mut metadata = result.metadata;
                     metadata.file_size_bytes = Some(entry.size_bytes);
@@ -1641,7 +1941,13 @@ fn fetch_metadata_for_entry(
                             shorter_title
                         );
                         if let Ok(Some(result)) = fetch_via_multi_search(
-                            client, &api_key, &shorter_title, parsed.year, parsed.season, parsed.episode,
+                            client,
+                            &tmdb,
+                            &shorter_title,
+                            parsed.year,
+                            parsed.season,
+                            parsed.episode,
+                            only,
                         ) {
                             let mut metadata = result.metadata;
                             metadata.file_size_bytes = Some(entry.size_bytes);
@@ -1664,7 +1970,13 @@ fn fetch_metadata_for_entry(
                         parsed.title
                     );
                     if let Ok(Some(result)) = fetch_via_multi_search(
-                        client, &api_key, &parsed.title, None, parsed.season, parsed.episode,
+                        client,
+                        &tmdb,
+                        &parsed.title,
+                        None,
+                        parsed.season,
+                        parsed.episode,
+                        only,
                     ) {
                         let mut metadata = result.metadata;
                         metadata.file_size_bytes = Some(entry.size_bytes);
@@ -1689,7 +2001,13 @@ fn fetch_metadata_for_entry(
                 if provider.api_key.is_empty() {
                     continue;
                 }
-                match fetch_from_omdb(client, &provider.api_key, &parsed.title, parsed.year) {
+                match fetch_from_omdb(
+                    client,
+                    &provider.api_key,
+                    &parsed.title,
+                    parsed.year,
+                    parsed.is_tv,
+                ) {
                     Ok(Some(result)) => {
                         let mut metadata = result.metadata;
                         metadata.file_size_bytes = Some(entry.size_bytes);
@@ -1726,13 +2044,11 @@ fn fetch_metadata_for_entry(
         "[Metadata] No API provider available for \"{}\", saving local metadata",
         entry.filename
     );
-    let metadata = build_local_metadata(entry);
-    let meta_dir = get_meta_dir(app, user_id, &entry.id)?;
-    fs::create_dir_all(&meta_dir).map_err(|e| format!("Failed to create meta dir: {}", e))?;
-    let meta_json =
-        serde_json::to_string_pretty(&metadata).map_err(|e| format!("Failed to serialize: {}", e))?;
-    fs::write(meta_dir.join("meta.json"), meta_json)
-        .map_err(|e| format!("Failed to write meta.json: {}", e))?;
+    let metadata = build_local_metadata(entry, folder.library_type);
+    let meta =
+        serde_json::to_value(&metadata).map_err(|e| format!("Failed to serialize: {}", e))?;
+    crate::db::save_metadata(app, user_id, &entry.id, &meta)
+        .map_err(|e| format!("Failed to save metadata: {}", e))?;
     log::info!(
         "[Metadata] Local metadata saved for {} (title: \"{}\")",
         entry.id,
@@ -1744,7 +2060,7 @@ fn fetch_metadata_for_entry(
 /// Fetch metadata for all entries that are missing it.
 /// Even without API providers, creates local metadata from filename + file info.
 pub fn fetch_missing_metadata(app: &AppHandle, user_id: &str) -> Result<String, String> {
-    let settings = media::get_settings(app.clone(), user_id.to_string())?;
+    let settings = media::load_settings(app, user_id)?;
     let cache_months = settings.metadata_cache_months.clamp(1, 6);
     let providers: Vec<media::MetadataProviderConfig> = settings
         .metadata_providers
@@ -1756,10 +2072,12 @@ pub fn fetch_missing_metadata(app: &AppHandle, user_id: &str) -> Result<String,
         log::info!("[Metadata] No API providers configured, will use local metadata only");
     }
 
-    let entries = media::get_media_library(app.clone(), user_id.to_string())?;
+    let entries = media::load_library(app, user_id)?;
     if entries.is_empty() {
         return Ok("No media entries to process".to_string());
     }
+    // Entries outside every configured folder keep the default options
+    let default_folder = media::MediaFolder::new(String::new());
 
     let client = reqwest::blocking::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
@@ -1771,23 +2089,58 @@ pub fn fetch_missing_metadata(app: &AppHandle, user_id: &str) -> Result<String,
     let mut skipped = 0;
 
     for entry in &entries {
-        if entry.id.is_empty() {
-            skipped += 1;
-            continue;
+        // The profile may have been locked or deleted while we were fetching
+        if !crate::session::is_active(app, user_id)
+            || media::get_user_dir_public(app, user_id).is_err()
+        {
+            log::info!(
+                "[Metadata] Session of user {} ended, aborting fetch",
+                user_id
+            );
+            break;
         }
 
-        if has_rich_metadata(app, user_id, &entry.id, cache_months) {
+        if entry.id.is_empty() {
             skipped += 1;
             continue;
         }
 
-        match fetch_metadata_for_entry(
-            &client,
-            app,
-            user_id,
-            entry,
-            &providers,
-        ) {
+        let folder = settings
+            .media
+            .folder_for(std::path::Path::new(&entry.path))
+            .unwrap_or(&default_folder);
+
+        // Extras, and folders without provider lookups, only need their local metadata
+        // once. Extras matched by a provider before they were recognized are redone.
+        let folder_providers: &[media::MetadataProviderConfig] = if entry.extra.is_some() {
+            match get_metadata(app, user_id, &entry.id) {
+                Ok(meta) if meta.get("media_type").and_then(|t| t.as_str()) == Some("extra") => {
+                    skipped += 1;
+                    continue;
+                }
+                // Drop the wrong match along with its images
+                Ok(_) => invalidate_metadata(app, user_id, std::slice::from_ref(entry)),
+                Err(_) => {}
+            }
+            &[]
+        } else if folder.uses_providers() {
+            if has_rich_metadata(app, user_id, &entry.id, cache_months) {
+                skipped += 1;
+                continue;
+            }
+            &providers
+        } else {
+            let has_local = crate::db::load_metadata(app, user_id, &entry.id)
+                .map(|meta| meta.is_some())
+                .unwrap_or(false);
+            if has_local {
+                skipped += 1;
+                continue;
+            }
+            &[]
+        };
+
+        match fetch_metadata_for_entry(&client, app, user_id, entry, folder, folder_providers) {
             Ok(true) => fetched += 1,
             Ok(false) => failed += 1,
             Err(e) => {
@@ -1801,7 +2154,7 @@ pub fn fetch_missing_metadata(app: &AppHandle, user_id: &str) -> Result<String,
         }
 
         // Small delay between requests to avoid rate limiting
-        if !providers.is_empty() {
+        if !folder_providers.is_empty() {
             std::thread::sleep(std::time::Duration::from_millis(250));
         }
     }
@@ -1820,6 +2173,8 @@ pub fn fetch_missing_metadata(app: &AppHandle, user_id: &str) -> Result<String,
 pub struct MetadataRetryState {
     stop_flag: std::sync::Arc<std::sync::atomic::AtomicBool>,
     thread: Option<std::thread::JoinHandle<()>>,
+    /// User the running retry thread fetches metadata for
+    user_id: Option<String>,
 }
 
 impl MetadataRetryState {
@@ -1827,6 +2182,7 @@ impl MetadataRetryState {
         Self {
             stop_flag: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
             thread: None,
+            user_id: None,
         }
     }
 }
@@ -1846,6 +2202,7 @@ pub fn start_metadata_retry(app: &AppHandle, user_id: &str) -> Result<(), String
 
     let stop_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     state.stop_flag = stop_flag.clone();
+    state.user_id = Some(user_id.to_string());
 
     let app_clone = app.clone();
     let user_id_clone = user_id.to_string();
@@ -1887,11 +2244,16 @@ pub fn start_metadata_retry(app: &AppHandle, user_id: &str) -> Result<(), String
     Ok(())
 }
 
-/// Stop the background metadata retry loop
-pub fn stop_metadata_retry(app: &AppHandle) -> Result<(), String> {
+/// Stop the background metadata retry loop if it runs for `user_id`
+pub fn stop_metadata_retry(app: &AppHandle, user_id: &str) -> Result<(), String> {
     let state = app.state::<std::sync::Arc<std::sync::Mutex<MetadataRetryState>>>();
     let mut state = state.lock().map_err(|e| e.to_string())?;
 
+    if state.user_id.as_deref().is_some_and(|id| id != user_id) {
+        return Ok(());
+    }
+    state.user_id = None;
+
     state
         .stop_flag
         .store(true, std::sync::atomic::Ordering::SeqCst);
@@ -1902,3 +2264,76 @@ pub fn stop_metadata_retry(app: &AppHandle) -> Result<(), String> {
     log::info!("[Metadata] Retry thread stopped");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use media::LibraryType;
+
+    fn parsed(
+        filename: &str,
+        library_type: LibraryType,
+    ) -> (String, Option<u32>, bool, Option<u32>, Option<u32>) {
+        let p = parse_filename(filename, library_type);
+        (p.title, p.year, p.is_tv, p.season, p.episode)
+    }
+
+    #[test]
+    fn movie_filenames_give_title_and_year() {
+        for (filename, title, year) in [
+            (
+                "Movie.Name.2024.1080p.BluRay.x264.mkv",
+                "Movie Name",
+                Some(2024),
+            ),
+            ("Movie Name (2024).mp4", "Movie Name", Some(2024)),
+            ("Movie_Name_2024_720p.mkv", "Movie Name", Some(2024)),
+            (
+                "Blade Runner 2049 (2017).mkv",
+                "Blade Runner 2049",
+                Some(2017),
+            ),
+            ("[Group] Movie.Name.1080p.WEB-DL.mkv", "Movie Name", None),
+        ] {
+            assert_eq!(
+                parsed(filename, LibraryType::Mixed),
+                (title.to_string(), year, false, None, None),
+                "{}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn episode_filenames_give_series_season_and_episode() {
+        assert_eq!(
+            parsed(
+                "Series.Name.S02E10.CUSTOM.MULTi.1080p.mkv",
+                LibraryType::Mixed
+            ),
+            ("Series Name".to_string(), None, true, Some(2), Some(10))
+        );
+        assert_eq!(
+            parsed("Series Name 2x05.mkv", LibraryType::Series),
+            ("Series Name".to_string(), None, true, Some(2), Some(5))
+        );
+        // "2x05" is only episode numbering in series folders
+        assert!(!parsed("Series Name 2x05.mkv", LibraryType::Mixed).2);
+    }
+
+    #[test]
+    fn library_type_decides_between_movie_and_episode() {
+        let (title, _, is_tv, ..) = parsed("Movie.S01E01.Special.mkv", LibraryType::Movies);
+        assert!(!is_tv);
+        assert_eq!(title, "Movie S01E01 Special");
+
+        // Everything in a series folder is an episode, numbered or not
+        let (title, _, is_tv, season, episode) =
+            parsed("Series Name - Pilot.mkv", LibraryType::Series);
+        assert!(is_tv);
+        assert_eq!(
+            (title.as_str(), season, episode),
+            ("Series Name Pilot", None, None)
+        );
+    }
+}
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::Mutex;
 
 /// Represents a connected peer
@@ -205,6 +205,20 @@ pub async fn peer_list(
     Ok(inner.peers.values().cloned().collect())
 }
 
+/// Shut down the endpoint and forget every known peer, e.g. when the profile
+/// that started it is deleted. No-op if the endpoint is not running.
+pub async fn reset(app: &AppHandle) {
+    let state = app.state::<Arc<Mutex<PeerState>>>();
+    let mut guard = state.lock().await;
+    if let Some(inner) = guard.inner.take() {
+        log::info!("[Peer] Resetting endpoint and {} known peer(s)", inner.peers.len());
+        if let Err(e) = inner.router.shutdown().await {
+            log::warn!("[Peer] Failed to shut down endpoint: {e}");
+        }
+        emit_peer_status(&HashMap::new(), app);
+    }
+}
+
 // ─── Helpers ──────────────────────────────────────────────────────
 
 fn emit_peer_status(peers: &HashMap<String, PeerInfo>, app: &AppHandle) {
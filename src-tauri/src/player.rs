@@ -1,27 +1,94 @@
 use std::path::Path;
 use std::process::Command;
+use tauri::AppHandle;
 
 #[tauri::command]
-pub fn player_open_vlc(path: String) -> Result<(), String> {
+pub fn player_open_vlc(app: AppHandle, path: String) -> Result<(), String> {
     log::info!("[Player] Opening in VLC: {}", path);
 
-    let file_path = Path::new(&path);
+    // Only files from the logged-in user's library can be opened
+    let user_id = crate::session::require_active(&app)?;
+    let normalized = path.replace('\\', "/");
+    let entry = crate::media::load_library(&app, &user_id)?
+        .into_iter()
+        .find(|e| e.path == normalized);
+    let entry = match entry {
+        Some(entry) => entry,
+        None => {
+            log::warn!(
+                "[Player] Refusing to open a file outside the library: {}",
+                path
+            );
+            return Err("This file is not part of your library".to_string());
+        }
+    };
 
-    // Verify the file actually exists
-    if !file_path.exists() {
-        return Err(format!("File not found: {}", path));
+    if !entry.available {
+        return Err("This title is on a drive or share that is not connected".to_string());
     }
 
-    // Canonicalize path to get a clean absolute path
-    let canonical = file_path.canonicalize().map_err(|e| format!("Invalid path: {}", e))?;
-    // Strip the \\?\ prefix that Windows canonicalize adds — VLC can't handle it
-    let canonical_str = canonical
-        .to_string_lossy()
-        .strip_prefix(r"\\?\")
-        .unwrap_or(&canonical.to_string_lossy())
-        .to_string();
+    // A Blu-ray/DVD folder plays the files of its main feature, and a
+    // multi-part title all of its parts, queued in order
+    let files = match &entry.disc {
+        Some(disc) => disc.main_feature.clone(),
+        None if !entry.parts.is_empty() => entry.parts.clone(),
+        None => vec![path.clone()],
+    };
 
-    log::info!("[Player] Canonical path: {}", canonical_str);
+    let mut args = Vec::with_capacity(files.len() * 2);
+
+    // Each file is queued with its subtitles as item options that only apply to
+    // that file: its forced track turned on, the others added as selectable
+    // tracks. VLC would only find those named after the video by itself, not
+    // "Subs/<video name>/2_English.srt" or the unnamed ones of a lone video.
+    let mut forced_tracks = 0;
+    for file in &files {
+        let canonical_str = canonical_path(file)?;
+        log::info!("[Player] Canonical path: {}", canonical_str);
+        args.push(canonical_str);
+
+        // The entry's tracks belong to its first file; the other parts of a
+        // multi-part title have their own, and disc streams none
+        let tracks = if file.replace('\\', "/") == entry.path {
+            entry.subtitles.clone()
+        } else if entry.parts.contains(file) {
+            crate::subtitles::discover(Path::new(file))
+        } else {
+            Vec::new()
+        };
+        let forced = tracks.iter().position(|t| t.forced);
+        let mut slaves = Vec::new();
+        for (i, track) in tracks.iter().enumerate() {
+            let path = match canonical_path(&track.path) {
+                Ok(path) => path,
+                Err(e) => {
+                    log::warn!("[Player] Skipping subtitle {}: {}", track.path, e);
+                    continue;
+                }
+            };
+            if Some(i) == forced {
+                args.push(format!(":sub-file={}", path));
+                forced_tracks += 1;
+            } else if path.contains('#') {
+                // Input slaves are separated by '#'
+                log::warn!(
+                    "[Player] Skipping subtitle with a '#' in its path: {}",
+                    path
+                );
+            } else {
+                slaves.push(path);
+            }
+        }
+        if !slaves.is_empty() {
+            args.push(format!(":input-slave={}", slaves.join("#")));
+        }
+    }
+    log::info!(
+        "[Player] {} file(s), {} subtitle track(s), {} forced",
+        files.len(),
+        entry.subtitles.len(),
+        forced_tracks
+    );
 
     // Try common VLC paths on Windows
     let vlc_paths = [
@@ -31,10 +98,7 @@ pub fn player_open_vlc(path: String) -> Result<(), String> {
     ];
 
     for vlc in &vlc_paths {
-        match Command::new(vlc)
-            .arg(&canonical_str)
-            .spawn()
-        {
+        match Command::new(vlc).args(&args).spawn() {
             Ok(_) => {
                 log::info!("[Player] VLC launched successfully with '{}'", vlc);
                 return Ok(());
@@ -48,3 +112,24 @@ pub fn player_open_vlc(path: String) -> Result<(), String> {
 
     Err("VLC is not installed or was not found. Please install VLC media player.".to_string())
 }
+
+/// Clean absolute path of an existing file, as VLC expects it
+fn canonical_path(file: &str) -> Result<String, String> {
+    let file_path = Path::new(file);
+
+    // Verify the file actually exists
+    if !file_path.exists() {
+        return Err(format!("File not found: {}", file));
+    }
+
+    // Canonicalize path to get a clean absolute path
+    let canonical = file_path
+        .canonicalize()
+        .map_err(|e| format!("Invalid path: {}", e))?;
+    // Strip the \\?\ prefix that Windows canonicalize adds — VLC can't handle it
+    let canonical_str = canonical.to_string_lossy();
+    Ok(canonical_str
+        .strip_prefix(r"\\?\")
+        .unwrap_or(&canonical_str)
+        .to_string())
+}
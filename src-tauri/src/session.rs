@@ -0,0 +1,232 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// How often the idle monitor checks the active session
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The profile currently unlocked with its PIN
+struct ActiveSession {
+    user_id: String,
+    started_at: String,
+    last_activity: Instant,
+    /// `None` disables the idle auto-lock
+    idle_timeout: Option<Duration>,
+}
+
+impl ActiveSession {
+    fn is_expired(&self) -> bool {
+        self.idle_timeout
+            .map(|timeout| self.last_activity.elapsed() >= timeout)
+            .unwrap_or(false)
+    }
+}
+
+/// State holding the active session (one unlocked profile at a time)
+pub struct SessionState {
+    active: Option<ActiveSession>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        Self { active: None }
+    }
+}
+
+/// Public view of the active session
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionInfo {
+    pub user_id: String,
+    pub started_at: String,
+    pub auto_lock_minutes: u32,
+}
+
+/// Payload emitted to the frontend when a session ends
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionLockedEvent {
+    pub user_id: String,
+    pub reason: String, // "logout", "idle", "switch"
+}
+
+fn idle_timeout_from_minutes(minutes: u32) -> Option<Duration> {
+    (minutes > 0).then(|| Duration::from_secs(minutes as u64 * 60))
+}
+
+/// Start a session for a user whose PIN was just verified.
+/// Any session of another user is closed first.
+pub fn open(app: &AppHandle, user_id: &str, auto_lock_minutes: u32) {
+    let previous = {
+        let state = app.state::<Arc<Mutex<SessionState>>>();
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = state.active.take().map(|s| s.user_id);
+        state.active = Some(ActiveSession {
+            user_id: user_id.to_string(),
+            started_at: chrono::Local::now().to_rfc3339(),
+            last_activity: Instant::now(),
+            idle_timeout: idle_timeout_from_minutes(auto_lock_minutes),
+        });
+        previous
+    };
+
+    if let Some(previous_id) = previous.filter(|id| id != user_id) {
+        release_user(app, &previous_id, "switch");
+    }
+
+    log::info!(
+        "[Session] Session opened for user {} (auto-lock: {} min)",
+        user_id,
+        auto_lock_minutes
+    );
+}
+
+/// Check that `user_id` is the unlocked profile and record the activity.
+/// An idle session is locked on the spot and the call is rejected.
+pub fn require(app: &AppHandle, user_id: &str) -> Result<(), String> {
+    let expired = {
+        let state = app.state::<Arc<Mutex<SessionState>>>();
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.active.as_mut() {
+            Some(session) if session.user_id == user_id => {
+                if session.is_expired() {
+                    state.active = None;
+                    true
+                } else {
+                    session.last_activity = Instant::now();
+                    return Ok(());
+                }
+            }
+            _ => false,
+        }
+    };
+
+    if expired {
+        release_user(app, user_id, "idle");
+        return Err("Session expired, please enter your PIN again".to_string());
+    }
+
+    log::warn!(
+        "[Session] Rejected unauthenticated call for user {}",
+        user_id
+    );
+    Err("Not logged in".to_string())
+}
+
+/// Like `require`, for commands that act on whoever is logged in.
+/// Returns the active user ID.
+pub fn require_active(app: &AppHandle) -> Result<String, String> {
+    let user_id = {
+        let state = app.state::<Arc<Mutex<SessionState>>>();
+        let state = state.lock().unwrap_or_else(|e| e.into_inner());
+        state.active.as_ref().map(|s| s.user_id.clone())
+    };
+    let user_id = user_id.ok_or_else(|| "Not logged in".to_string())?;
+    require(app, &user_id)?;
+    Ok(user_id)
+}
+
+/// Whether `user_id` has an unexpired session, without counting as activity:
+/// background work checks it to stop once the user is locked out
+pub fn is_active(app: &AppHandle, user_id: &str) -> bool {
+    let state = app.state::<Arc<Mutex<SessionState>>>();
+    let state = state.lock().unwrap_or_else(|e| e.into_inner());
+    state
+        .active
+        .as_ref()
+        .is_some_and(|s| s.user_id == user_id && !s.is_expired())
+}
+
+/// Change the idle timeout of the active session (after a settings update)
+pub fn set_auto_lock(app: &AppHandle, user_id: &str, auto_lock_minutes: u32) {
+    let state = app.state::<Arc<Mutex<SessionState>>>();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(session) = state.active.as_mut().filter(|s| s.user_id == user_id) {
+        session.idle_timeout = idle_timeout_from_minutes(auto_lock_minutes);
+    }
+}
+
+/// Get the active session, if any (does not count as activity)
+pub fn current(app: &AppHandle) -> Option<SessionInfo> {
+    let state = app.state::<Arc<Mutex<SessionState>>>();
+    let state = state.lock().unwrap_or_else(|e| e.into_inner());
+    state.active.as_ref().map(|s| SessionInfo {
+        user_id: s.user_id.clone(),
+        started_at: s.started_at.clone(),
+        auto_lock_minutes: s
+            .idle_timeout
+            .map(|t| (t.as_secs() / 60) as u32)
+            .unwrap_or(0),
+    })
+}
+
+/// End the session of a user (explicit logout, deletion)
+pub fn close(app: &AppHandle, user_id: &str, reason: &str) {
+    let was_active = {
+        let state = app.state::<Arc<Mutex<SessionState>>>();
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.active.as_ref().is_some_and(|s| s.user_id == user_id) {
+            state.active = None;
+            true
+        } else {
+            false
+        }
+    };
+
+    if was_active {
+        release_user(app, user_id, reason);
+    }
+}
+
+/// Stop everything that runs on behalf of a user and forget their keys
+fn release_user(app: &AppHandle, user_id: &str, reason: &str) {
+    if let Err(e) = crate::watcher::stop_watching(app, user_id) {
+        log::warn!(
+            "[Session] Failed to stop watcher for user {}: {}",
+            user_id,
+            e
+        );
+    }
+    // Fetches and garbage collections running in other threads see the session
+    // gone and stop at their next entry
+    if let Err(e) = crate::metadata::stop_metadata_retry(app, user_id) {
+        log::warn!(
+            "[Session] Failed to stop metadata retry for user {}: {}",
+            user_id,
+            e
+        );
+    }
+    crate::vault::lock(app, user_id);
+
+    let _ = app.emit(
+        "session-locked",
+        SessionLockedEvent {
+            user_id: user_id.to_string(),
+            reason: reason.to_string(),
+        },
+    );
+
+    log::info!("[Session] Session closed for user {} ({})", user_id, reason);
+}
+
+/// Background thread locking the active session once it has been idle too long
+pub fn spawn_idle_monitor(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(IDLE_CHECK_INTERVAL);
+
+        let expired_user = {
+            let state = app.state::<Arc<Mutex<SessionState>>>();
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.active.as_ref().is_some_and(|s| s.is_expired()) {
+                state.active.take().map(|s| s.user_id)
+            } else {
+                None
+            }
+        };
+
+        if let Some(user_id) = expired_user {
+            log::info!("[Session] Auto-locking idle session for user {}", user_id);
+            release_user(&app, &user_id, "idle");
+        }
+    });
+}
@@ -0,0 +1,392 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sidecar subtitle file extensions
+pub const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt", "sub", "idx"];
+
+/// Folders next to a video holding its subtitles ("Subs/English.srt",
+/// "Subs/<video name>/2_English.srt")
+const SUBTITLE_FOLDERS: &[&str] = &["subs", "subtitles"];
+
+/// An external subtitle track of a `MediaEntry`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SubtitleTrack {
+    pub path: String,
+    /// File extension: srt, ass, ssa, vtt, sub or idx
+    pub format: String,
+    /// ISO 639-1 code ("fr", "pt-BR"), or the tag as written when unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Only covers foreign-language parts of the audio
+    #[serde(default)]
+    pub forced: bool,
+    /// Subtitles for the deaf and hard of hearing (closed captions)
+    #[serde(default)]
+    pub sdh: bool,
+    /// Remaining tags of the name ("Commentary")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Language names and ISO 639-2 codes found in subtitle names, by ISO 639-1 code
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("en", &["en", "eng", "english"]),
+    (
+        "fr",
+        &[
+            "fr",
+            "fre",
+            "fra",
+            "french",
+            "francais",
+            "français",
+            "vf",
+            "vff",
+        ],
+    ),
+    ("de", &["de", "ger", "deu", "german", "deutsch"]),
+    ("it", &["it", "ita", "italian", "italiano"]),
+    ("ja", &["ja", "jpn", "japanese"]),
+    ("es", &["es", "spa", "spanish", "espanol", "español"]),
+    ("pt", &["pt", "por", "portuguese", "portugues", "português"]),
+    ("pt-BR", &["pt-br", "pob", "pb", "brazilian"]),
+    ("nl", &["nl", "dut", "nld", "dutch"]),
+    ("sv", &["sv", "swe", "swedish"]),
+    ("no", &["no", "nor", "nob", "norwegian"]),
+    ("da", &["da", "dan", "danish"]),
+    ("fi", &["fi", "fin", "finnish"]),
+    ("pl", &["pl", "pol", "polish"]),
+    ("ru", &["ru", "rus", "russian"]),
+    ("uk", &["uk", "ukr", "ukrainian"]),
+    ("cs", &["cs", "cze", "ces", "czech"]),
+    ("hu", &["hu", "hun", "hungarian"]),
+    ("ro", &["ro", "rum", "ron", "romanian"]),
+    ("el", &["el", "gre", "ell", "greek"]),
+    ("tr", &["tr", "tur", "turkish"]),
+    ("ar", &["ar", "ara", "arabic"]),
+    ("he", &["he", "heb", "hebrew"]),
+    ("zh", &["zh", "chi", "zho", "chinese"]),
+    ("ko", &["ko", "kor", "korean"]),
+    ("hi", &["hi", "hin", "hindi"]),
+];
+
+const FORCED_TAGS: &[&str] = &["forced", "foreign"];
+const SDH_TAGS: &[&str] = &["sdh", "cc"];
+/// "Hearing impaired" next to a language ("en.hi"), Hindi on its own
+const HI_TAG: &str = "hi";
+
+/// Whether a file is a subtitle, from its extension
+pub fn is_subtitle(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| SUBTITLE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Whether a folder name is one of the subtitle folders
+fn is_subtitle_folder(name: &str) -> bool {
+    SUBTITLE_FOLDERS
+        .iter()
+        .any(|f| f.eq_ignore_ascii_case(name))
+}
+
+/// Directories whose videos a subtitle file may belong to: its own, and the
+/// one above its subtitle folder ("Subs/x.srt", "Subs/<video name>/x.srt")
+pub fn video_dirs_of(path: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = path.parent().into_iter().map(Path::to_path_buf).collect();
+    for ancestor in path.ancestors().skip(1).take(2) {
+        let name = ancestor.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if is_subtitle_folder(name) {
+            dirs.extend(ancestor.parent().map(Path::to_path_buf));
+        }
+    }
+    dirs
+}
+
+fn lowercase_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Subtitle files found in a directory and its subtitle folders, listed once
+/// and shared by all the videos of the directory
+#[derive(Default)]
+pub struct Sidecars {
+    /// Subtitles next to the videos
+    direct: Vec<PathBuf>,
+    /// Subtitles directly in a subtitle folder
+    folder: Vec<PathBuf>,
+    /// Subtitles in a per-video subtitle folder, by lowercased folder name
+    per_video: HashMap<String, Vec<PathBuf>>,
+}
+
+fn subtitle_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|rd| {
+            rd.flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && is_subtitle(p))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Sidecars {
+    /// Sidecars of `dir`, from the names of its subtitle files and subdirectories
+    /// (as listed by the scanner); subtitle folders are read here
+    pub fn list(dir: &Path, subtitle_names: &[String], subdirs: &[String]) -> Self {
+        let mut sidecars = Sidecars {
+            direct: subtitle_names.iter().map(|n| dir.join(n)).collect(),
+            ..Default::default()
+        };
+        for name in subdirs.iter().filter(|n| is_subtitle_folder(n)) {
+            let folder = dir.join(name);
+            sidecars.folder.extend(subtitle_files(&folder));
+            if let Ok(rd) = fs::read_dir(&folder) {
+                for entry in rd.flatten().filter(|e| e.path().is_dir()) {
+                    let video = entry.file_name().to_string_lossy().to_lowercase();
+                    sidecars
+                        .per_video
+                        .entry(video)
+                        .or_default()
+                        .extend(subtitle_files(&entry.path()));
+                }
+            }
+        }
+        sidecars
+    }
+
+    /// Sidecars of `dir`, reading it
+    pub fn read(dir: &Path) -> Self {
+        let mut subtitle_names = Vec::new();
+        let mut subdirs = Vec::new();
+        if let Ok(rd) = fs::read_dir(dir) {
+            for entry in rd.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = entry.path();
+                if path.is_dir() {
+                    subdirs.push(name);
+                } else if is_subtitle(&path) {
+                    subtitle_names.push(name);
+                }
+            }
+        }
+        Self::list(dir, &subtitle_names, &subdirs)
+    }
+
+    /// Subtitle tracks of a video. Files are matched by name ("Movie.fr.srt" for
+    /// "Movie.mkv"); a lone video in its folder also gets the unnamed ones of
+    /// its subtitle folder ("Subs/English.srt").
+    pub fn tracks_for(&self, video: &Path, lone_video: bool) -> Vec<SubtitleTrack> {
+        let stem = lowercase_stem(video);
+        let mut tracks = Vec::new();
+
+        for path in self.direct.iter().chain(&self.folder) {
+            let sub_stem = lowercase_stem(path);
+            if let Some(rest) = sub_stem.strip_prefix(&stem) {
+                if rest.is_empty() || rest.starts_with('.') {
+                    tracks.push(parse_track(path, rest));
+                }
+            } else if lone_video && self.folder.contains(path) {
+                tracks.push(parse_track(path, &sub_stem));
+            }
+        }
+        if let Some(files) = self.per_video.get(&stem) {
+            tracks.extend(files.iter().map(|p| parse_track(p, &lowercase_stem(p))));
+        }
+
+        // A VobSub .sub is read through its .idx
+        let idx_stems: Vec<String> = tracks
+            .iter()
+            .filter(|t| t.format == "idx")
+            .map(|t| t.path[..t.path.len() - 4].to_lowercase())
+            .collect();
+        tracks.retain(|t| {
+            t.format != "sub" || !idx_stems.contains(&t.path[..t.path.len() - 4].to_lowercase())
+        });
+
+        tracks.sort_by(|a, b| a.path.cmp(&b.path));
+        tracks
+    }
+}
+
+fn language_of(tag: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(_, names)| names.contains(&tag))
+        .map(|(code, _)| *code)
+}
+
+/// Build a track from the tags of a subtitle name ("fr.forced", "2_english.sdh")
+fn parse_track(path: &Path, tags: &str) -> SubtitleTrack {
+    let mut language = None;
+    let mut forced = false;
+    let mut sdh = false;
+    let mut hi = false;
+    let mut rest: Vec<&str> = Vec::new();
+
+    for tag in tags.split(['.', ' ']).filter(|t| !t.is_empty()) {
+        // "2_english": numbered tracks of release subtitle folders
+        let tag = tag
+            .split_once('_')
+            .filter(|(n, _)| n.chars().all(|c| c.is_ascii_digit()))
+            .map_or(tag, |(_, t)| t);
+        if tag == HI_TAG {
+            hi = true;
+        } else if FORCED_TAGS.contains(&tag) {
+            forced = true;
+        } else if SDH_TAGS.contains(&tag) {
+            sdh = true;
+        } else if language.is_none() && language_of(tag).is_some() {
+            language = language_of(tag).map(str::to_string);
+        } else if tag != "default" {
+            rest.push(tag);
+        }
+    }
+
+    if hi {
+        match language {
+            Some(_) => sdh = true,
+            None => language = Some(HI_TAG.to_string()),
+        }
+    }
+
+    // An unknown two or three letter tag is most likely a language code
+    if language.is_none()
+        && rest.len() == 1
+        && (2..=3).contains(&rest[0].len())
+        && rest[0].chars().all(|c| c.is_ascii_alphabetic())
+    {
+        language = rest.pop().map(str::to_string);
+    }
+
+    let format = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    SubtitleTrack {
+        path: path.to_string_lossy().replace('\\', "/"),
+        format,
+        language,
+        forced,
+        sdh,
+        title: (!rest.is_empty()).then(|| rest.join(" ")),
+    }
+}
+
+/// Subtitle tracks of a video, reading its directory (used by the watcher)
+pub fn discover(video: &Path) -> Vec<SubtitleTrack> {
+    let dir = match video.parent() {
+        Some(dir) => dir,
+        None => return Vec::new(),
+    };
+    let videos = fs::read_dir(dir)
+        .map(|rd| {
+            rd.flatten()
+                .filter(|e| {
+                    e.path()
+                        .extension()
+                        .and_then(|x| x.to_str())
+                        .is_some_and(|x| {
+                            crate::media::VIDEO_EXTENSIONS.contains(&x.to_lowercase().as_str())
+                        })
+                })
+                .count()
+        })
+        .unwrap_or(0);
+    Sidecars::read(dir).tracks_for(video, videos <= 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::testing::TempDir;
+
+    fn track(tags: &str) -> SubtitleTrack {
+        parse_track(Path::new("/m/Movie.SRT"), tags)
+    }
+
+    #[test]
+    fn tags_give_language_flags_and_title() {
+        let fr = track(".fr.forced");
+        assert_eq!(fr.language.as_deref(), Some("fr"));
+        assert!(fr.forced && !fr.sdh);
+        assert_eq!(fr.format, "srt");
+
+        let en = track("2_english.sdh");
+        assert_eq!(en.language.as_deref(), Some("en"));
+        assert!(en.sdh && !en.forced);
+
+        let commentary = track(".eng.default.director commentary");
+        assert_eq!(commentary.language.as_deref(), Some("en"));
+        assert_eq!(commentary.title.as_deref(), Some("director commentary"));
+
+        assert_eq!(track(".pt-br").language.as_deref(), Some("pt-BR"));
+        // An unknown short tag is taken as the language code
+        assert_eq!(track(".tlh").language.as_deref(), Some("tlh"));
+        assert_eq!(track("").language, None);
+    }
+
+    #[test]
+    fn hi_is_hearing_impaired_next_to_a_language_and_hindi_alone() {
+        let en = track(".en.hi");
+        assert_eq!(en.language.as_deref(), Some("en"));
+        assert!(en.sdh);
+
+        let hindi = track(".hi");
+        assert_eq!(hindi.language.as_deref(), Some("hi"));
+        assert!(!hindi.sdh);
+        assert_eq!(track(".hindi.forced").language.as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn videos_get_the_subtitles_named_after_them() {
+        let dir = TempDir::new();
+        let video = dir.write("Movie.mkv", "");
+        for name in [
+            "Movie.fr.srt",
+            "Movie.en.forced.srt",
+            "Movie.srt",
+            "Movie.idx",
+            "Movie.sub",
+            "Movie2.srt",
+            "Other.en.srt",
+            "Subs/English.srt",
+            "Subs/Movie/2_German.srt",
+            "Subs/Other/3_Dutch.srt",
+        ] {
+            dir.write(name, "");
+        }
+        let sidecars = Sidecars::read(dir.path());
+        let names = |lone_video: bool| -> Vec<(String, Option<String>, bool)> {
+            sidecars
+                .tracks_for(&video, lone_video)
+                .into_iter()
+                .map(|t| {
+                    let path = Path::new(&t.path).strip_prefix(dir.path()).unwrap();
+                    (path.to_string_lossy().to_string(), t.language, t.forced)
+                })
+                .collect()
+        };
+        let some = |language: &str| Some(language.to_string());
+
+        assert_eq!(
+            names(false),
+            vec![
+                ("Movie.en.forced.srt".to_string(), some("en"), true),
+                ("Movie.fr.srt".to_string(), some("fr"), false),
+                // The .sub of a VobSub pair is left out
+                ("Movie.idx".to_string(), None, false),
+                ("Movie.srt".to_string(), None, false),
+                ("Subs/Movie/2_German.srt".to_string(), some("de"), false),
+            ]
+        );
+        // A lone video also gets the unnamed subtitles of its subtitle folder
+        assert!(names(true).contains(&("Subs/English.srt".to_string(), some("en"), false)));
+        assert_eq!(names(true).len(), 6);
+    }
+}
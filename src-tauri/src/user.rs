@@ -2,11 +2,15 @@ use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
 };
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
 use uuid::Uuid;
 
@@ -15,6 +19,9 @@ pub struct UserProfile {
     pub id: String,
     pub username: String,
     pub created_at: String,
+    /// How the profile is unlocked (profiles created before this was configurable use a 4-digit PIN)
+    #[serde(default)]
+    pub credential: CredentialPolicy,
 }
 
 /// Info returned by check_user_exists
@@ -22,10 +29,225 @@ pub struct UserProfile {
 pub struct ExistingUser {
     pub id: String,
     pub username: String,
+    /// Lets the login screen show the right input before the profile is unlocked
+    #[serde(default)]
+    pub credential: CredentialPolicy,
 }
 
-/// Derive a 256-bit key from a 4-digit PIN using SHA-256
-fn derive_key(pin: &str) -> [u8; 32] {
+/// Shortest numeric PIN allowed
+const PIN_MIN_DIGITS: u8 = 4;
+/// Longest numeric PIN allowed
+const PIN_MAX_DIGITS: u8 = 12;
+/// Shortest passphrase allowed (in characters)
+const PASSPHRASE_MIN_CHARS: usize = 8;
+
+/// Kind of secret protecting a profile
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CredentialPolicy {
+    /// Numeric PIN of a fixed length
+    Pin { digits: u8 },
+    /// Free-form passphrase
+    Passphrase,
+    /// No secret at all, for shared screens. Keys are derived from an empty secret,
+    /// so the profile is only obfuscated on disk.
+    #[serde(rename = "none")]
+    Unprotected,
+}
+
+impl Default for CredentialPolicy {
+    fn default() -> Self {
+        CredentialPolicy::Pin { digits: 4 }
+    }
+}
+
+impl CredentialPolicy {
+    /// Check that a new secret matches this policy
+    fn validate(&self, secret: &str) -> Result<(), String> {
+        match self {
+            CredentialPolicy::Pin { digits } => {
+                if !(PIN_MIN_DIGITS..=PIN_MAX_DIGITS).contains(digits) {
+                    return Err(format!(
+                        "PIN length must be between {} and {} digits",
+                        PIN_MIN_DIGITS, PIN_MAX_DIGITS
+                    ));
+                }
+                if secret.len() != *digits as usize || !secret.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(format!("PIN must be exactly {} digits", digits));
+                }
+            }
+            CredentialPolicy::Passphrase => {
+                if secret.chars().count() < PASSPHRASE_MIN_CHARS {
+                    return Err(format!(
+                        "Passphrase must be at least {} characters",
+                        PASSPHRASE_MIN_CHARS
+                    ));
+                }
+            }
+            CredentialPolicy::Unprotected => {}
+        }
+        Ok(())
+    }
+
+    /// The secret actually fed to the key derivation
+    fn secret<'a>(&self, input: &'a str) -> &'a str {
+        match self {
+            CredentialPolicy::Unprotected => "",
+            _ => input,
+        }
+    }
+}
+
+/// Argon2id memory cost (KiB) used when writing new PIN records
+const KDF_MEMORY_KIB: u32 = 64 * 1024;
+/// Argon2id iteration count used when writing new PIN records
+const KDF_ITERATIONS: u32 = 3;
+/// Argon2id parallelism used when writing new PIN records
+const KDF_PARALLELISM: u32 = 1;
+/// Length of the random per-user salt
+const KDF_SALT_LEN: usize = 16;
+/// Current version of the pin.hash record format
+const PIN_RECORD_VERSION: u32 = 3;
+/// Random bytes in a recovery code (160 bits)
+const RECOVERY_CODE_BYTES: usize = 20;
+/// Alphabet used to display recovery codes (Crockford base32, no I/L/O/U)
+const RECOVERY_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Credential record stored (as JSON) in pin.hash.
+/// Older installs stored the raw 32-byte SHA-256 output instead, see `StoredPin::Legacy`.
+#[derive(Serialize, Deserialize, Clone)]
+struct PinRecord {
+    version: u32,
+    kdf: String,
+    /// Base64-encoded random salt, unique per user
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    /// Base64-encoded verification key (first half of the Argon2id output)
+    verifier: String,
+    /// Base64-encoded profile key, encrypted with the second half of the Argon2id output.
+    /// Missing in version 2 records, where that half was the profile key itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wrapped_key: Option<String>,
+    /// Kind of secret this record was derived from
+    #[serde(default)]
+    credential: CredentialPolicy,
+}
+
+/// Recovery record stored (as JSON) in recovery.json: a second copy of the
+/// profile key, wrapped with a key derived from the recovery code
+#[derive(Serialize, Deserialize, Clone)]
+struct RecoveryRecord {
+    kdf: String,
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    wrapped_key: String,
+    created_at: String,
+}
+
+/// Returned by create_user: the profile plus its recovery code, which is never shown again
+#[derive(Serialize, Clone)]
+pub struct CreatedUser {
+    #[serde(flatten)]
+    pub profile: UserProfile,
+    pub recovery_code: String,
+}
+
+impl PinRecord {
+    /// Whether this record was written with weaker parameters than the current defaults
+    fn needs_rehash(&self) -> bool {
+        self.version < PIN_RECORD_VERSION
+            || self.m_cost < KDF_MEMORY_KIB
+            || self.t_cost < KDF_ITERATIONS
+            || self.p_cost < KDF_PARALLELISM
+    }
+}
+
+/// Contents of pin.hash, either the current record or the pre-Argon2 raw hash
+enum StoredPin {
+    Current(PinRecord),
+    Legacy(Vec<u8>),
+}
+
+/// Keys derived from a PIN: one is stored for verification, the other never leaves memory
+struct DerivedKeys {
+    verify: [u8; 32],
+    encrypt: [u8; 32],
+}
+
+/// Derive the verification and encryption keys from a PIN with Argon2id.
+/// A single 64-byte output is split in two so the stored verifier reveals nothing
+/// about the encryption key.
+fn derive_keys(
+    pin: &str,
+    salt: &[u8],
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+) -> Result<DerivedKeys, String> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(64)).map_err(|e| e.to_string())?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+    let mut output = [0u8; 64];
+    argon2
+        .hash_password_into(pin.as_bytes(), salt, &mut output)
+        .map_err(|e| e.to_string())?;
+
+    let mut keys = DerivedKeys {
+        verify: [0u8; 32],
+        encrypt: [0u8; 32],
+    };
+    keys.verify.copy_from_slice(&output[..32]);
+    keys.encrypt.copy_from_slice(&output[32..]);
+    Ok(keys)
+}
+
+/// Derive the keys for a PIN using the salt and parameters stored in its record
+fn derive_keys_for_record(pin: &str, record: &PinRecord) -> Result<DerivedKeys, String> {
+    if record.kdf != "argon2id" {
+        return Err(format!(
+            "Unsupported key derivation function: {}",
+            record.kdf
+        ));
+    }
+    let salt = BASE64.decode(&record.salt).map_err(|e| e.to_string())?;
+    derive_keys(pin, &salt, record.m_cost, record.t_cost, record.p_cost)
+}
+
+/// Generate a random salt for a new credential record
+fn generate_salt() -> [u8; KDF_SALT_LEN] {
+    let mut salt = [0u8; KDF_SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+/// Generate a new random profile key. It encrypts profile.enc and the vault, and is
+/// only ever stored wrapped (by the PIN and by the recovery code).
+fn generate_profile_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+/// Encrypt the profile key with a wrapping key, base64-encoded for JSON storage
+fn wrap_key(profile_key: &[u8; 32], wrapping_key: &[u8; 32]) -> Result<String, String> {
+    Ok(BASE64.encode(encrypt(profile_key, wrapping_key)?))
+}
+
+/// Decrypt a wrapped profile key. Fails if the wrapping key is wrong.
+fn unwrap_key(wrapped: &str, wrapping_key: &[u8; 32]) -> Result<[u8; 32], String> {
+    let data = BASE64.decode(wrapped).map_err(|e| e.to_string())?;
+    let plain = decrypt(&data, wrapping_key)?;
+    plain
+        .try_into()
+        .map_err(|_| "Invalid wrapped key".to_string())
+}
+
+/// Legacy key derivation (SHA-256 with a fixed salt), kept only to migrate old accounts
+fn derive_legacy_key(pin: &str) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(pin.as_bytes());
     hasher.update(b"popcorn-hero-salt-2026");
@@ -35,10 +257,20 @@ fn derive_key(pin: &str) -> [u8; 32] {
     key
 }
 
+/// Compare two byte slices without short-circuiting on the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
 /// Encrypt data using AES-256-GCM
-fn encrypt(data: &[u8], pin: &str) -> Result<Vec<u8>, String> {
-    let key = derive_key(pin);
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+pub fn encrypt(data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
 
     let mut nonce_bytes = [0u8; 12];
     rand::thread_rng().fill_bytes(&mut nonce_bytes);
@@ -53,13 +285,12 @@ fn encrypt(data: &[u8], pin: &str) -> Result<Vec<u8>, String> {
 }
 
 /// Decrypt data using AES-256-GCM
-fn decrypt(data: &[u8], pin: &str) -> Result<Vec<u8>, String> {
+pub fn decrypt(data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, String> {
     if data.len() < 12 {
         return Err("Invalid encrypted data".to_string());
     }
 
-    let key = derive_key(pin);
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
 
     let nonce = Nonce::from_slice(&data[..12]);
     let ciphertext = &data[12..];
@@ -67,6 +298,376 @@ fn decrypt(data: &[u8], pin: &str) -> Result<Vec<u8>, String> {
     cipher.decrypt(nonce, ciphertext).map_err(|e| e.to_string())
 }
 
+/// Write a file through a temporary sibling so a crash never leaves it half-written
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data).map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|e| e.to_string())
+}
+
+/// Read and parse pin.hash, recognising the legacy raw-hash format
+fn read_pin_record(user_dir: &Path) -> Result<StoredPin, String> {
+    let raw = fs::read(user_dir.join("pin.hash")).map_err(|e| e.to_string())?;
+    if let Ok(record) = serde_json::from_slice::<PinRecord>(&raw) {
+        return Ok(StoredPin::Current(record));
+    }
+    if raw.len() == 32 {
+        return Ok(StoredPin::Legacy(raw));
+    }
+    Err("Corrupted PIN record".to_string())
+}
+
+/// Generate a fresh salt for `pin` and write a pin.hash wrapping `profile_key`.
+/// profile.enc is untouched, so this is all a PIN change needs.
+fn write_pin_record(
+    user_dir: &Path,
+    pin: &str,
+    credential: &CredentialPolicy,
+    profile_key: &[u8; 32],
+) -> Result<(), String> {
+    let salt = generate_salt();
+    let keys = derive_keys(
+        credential.secret(pin),
+        &salt,
+        KDF_MEMORY_KIB,
+        KDF_ITERATIONS,
+        KDF_PARALLELISM,
+    )?;
+    let record = PinRecord {
+        version: PIN_RECORD_VERSION,
+        kdf: "argon2id".to_string(),
+        salt: BASE64.encode(salt),
+        m_cost: KDF_MEMORY_KIB,
+        t_cost: KDF_ITERATIONS,
+        p_cost: KDF_PARALLELISM,
+        verifier: BASE64.encode(keys.verify),
+        wrapped_key: Some(wrap_key(profile_key, &keys.encrypt)?),
+        credential: credential.clone(),
+    };
+
+    let record_json = serde_json::to_vec_pretty(&record).map_err(|e| e.to_string())?;
+    write_atomic(&user_dir.join("pin.hash"), &record_json)
+}
+
+/// Write pin.hash for `pin` (following the profile's credential policy)
+/// and encrypt the profile with `profile_key`
+fn write_credentials(
+    user_dir: &Path,
+    pin: &str,
+    profile: &UserProfile,
+    profile_key: &[u8; 32],
+) -> Result<(), String> {
+    // pin.hash is written first. A crash before profile.enc is replaced during a
+    // legacy migration leaves profile.enc under the legacy key, which check_pin
+    // tries when the new key fails. A new profile gets meta.json last, so it is
+    // not listed until both files exist.
+    write_pin_record(user_dir, pin, &profile.credential, profile_key)?;
+    write_profile(user_dir, profile, profile_key)
+}
+
+/// Encode random bytes as a recovery code, in dash-separated groups of four
+fn format_recovery_code(bytes: &[u8]) -> String {
+    let mut chars = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            chars.push(RECOVERY_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        chars.push(RECOVERY_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    chars
+        .chunks(4)
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Normalize a typed recovery code: case, spaces and dashes do not matter,
+/// and the look-alike letters O, I and L are read as digits
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+/// Generate a new recovery code wrapping `profile_key` and write recovery.json,
+/// replacing any previous code. Returns the code to show to the user.
+fn write_recovery(user_dir: &Path, profile_key: &[u8; 32]) -> Result<String, String> {
+    let mut code_bytes = [0u8; RECOVERY_CODE_BYTES];
+    rand::thread_rng().fill_bytes(&mut code_bytes);
+    let code = format_recovery_code(&code_bytes);
+
+    let salt = generate_salt();
+    let keys = derive_keys(
+        &normalize_recovery_code(&code),
+        &salt,
+        KDF_MEMORY_KIB,
+        KDF_ITERATIONS,
+        KDF_PARALLELISM,
+    )?;
+    let record = RecoveryRecord {
+        kdf: "argon2id".to_string(),
+        salt: BASE64.encode(salt),
+        m_cost: KDF_MEMORY_KIB,
+        t_cost: KDF_ITERATIONS,
+        p_cost: KDF_PARALLELISM,
+        wrapped_key: wrap_key(profile_key, &keys.encrypt)?,
+        created_at: chrono::Local::now().to_rfc3339(),
+    };
+
+    let record_json = serde_json::to_vec_pretty(&record).map_err(|e| e.to_string())?;
+    write_atomic(&user_dir.join("recovery.json"), &record_json)?;
+    Ok(code)
+}
+
+/// Recover the profile key with a recovery code. Returns `None` for a wrong code.
+fn unwrap_with_recovery(user_dir: &Path, code: &str) -> Result<Option<[u8; 32]>, String> {
+    let path = user_dir.join("recovery.json");
+    if !path.exists() {
+        return Err("No recovery code was set up for this profile".to_string());
+    }
+    let raw = fs::read(&path).map_err(|e| e.to_string())?;
+    let record: RecoveryRecord = serde_json::from_slice(&raw).map_err(|e| e.to_string())?;
+    if record.kdf != "argon2id" {
+        return Err(format!(
+            "Unsupported key derivation function: {}",
+            record.kdf
+        ));
+    }
+
+    let salt = BASE64.decode(&record.salt).map_err(|e| e.to_string())?;
+    let keys = derive_keys(
+        &normalize_recovery_code(code),
+        &salt,
+        record.m_cost,
+        record.t_cost,
+        record.p_cost,
+    )?;
+    Ok(unwrap_key(&record.wrapped_key, &keys.encrypt).ok())
+}
+
+/// Encrypt and write profile.enc with the given key
+fn write_profile(user_dir: &Path, profile: &UserProfile, key: &[u8; 32]) -> Result<(), String> {
+    let profile_json = serde_json::to_string(profile).map_err(|e| e.to_string())?;
+    let encrypted = encrypt(profile_json.as_bytes(), key)?;
+    write_atomic(&user_dir.join("profile.enc"), &encrypted)
+}
+
+/// Decrypt profile.enc with the given key
+fn read_profile(user_dir: &Path, key: &[u8; 32]) -> Result<UserProfile, String> {
+    let encrypted = fs::read(user_dir.join("profile.enc")).map_err(|e| e.to_string())?;
+    let decrypted = decrypt(&encrypted, key)?;
+    serde_json::from_slice(&decrypted).map_err(|e| e.to_string())
+}
+
+/// A profile that was successfully unlocked with its PIN
+struct UnlockedProfile {
+    profile: UserProfile,
+    /// Profile key (encrypts profile.enc, the vault key derives from it)
+    key: [u8; 32],
+    /// Previous profile key, when the unlock had to replace it (legacy migration)
+    replaced_key: Option<[u8; 32]>,
+}
+
+/// Check a PIN against pin.hash and decrypt the profile. Returns `None` for a wrong PIN.
+/// Accounts still using the legacy SHA-256 hash (or outdated Argon2 parameters)
+/// are transparently upgraded on success.
+fn check_pin(user_dir: &Path, pin: &str) -> Result<Option<UnlockedProfile>, String> {
+    match read_pin_record(user_dir)? {
+        StoredPin::Current(record) => {
+            let pin = record.credential.secret(pin);
+            let keys = derive_keys_for_record(pin, &record)?;
+            let verifier = BASE64.decode(&record.verifier).map_err(|e| e.to_string())?;
+            if !constant_time_eq(&keys.verify, &verifier) {
+                return Ok(None);
+            }
+
+            // Version 2 records used the PIN-derived key as the profile key directly
+            let key = match &record.wrapped_key {
+                Some(wrapped) => unwrap_key(wrapped, &keys.encrypt)?,
+                None => keys.encrypt,
+            };
+
+            let mut replaced_key = None;
+            let profile = match read_profile(user_dir, &key) {
+                Ok(profile) => profile,
+                Err(_) => {
+                    // An interrupted migration can leave profile.enc under the legacy key
+                    let legacy_key = derive_legacy_key(pin);
+                    let profile = read_profile(user_dir, &legacy_key)?;
+                    log::warn!("[User] Recovered profile left over from an interrupted migration");
+                    write_profile(user_dir, &profile, &key)?;
+                    replaced_key = Some(legacy_key);
+                    profile
+                }
+            };
+
+            // Re-wrapping keeps the profile key, so nothing else needs re-encrypting
+            if record.needs_rehash() {
+                log::info!(
+                    "[User] Upgrading PIN record to version {} for user {}",
+                    PIN_RECORD_VERSION,
+                    profile.id
+                );
+                write_pin_record(user_dir, pin, &record.credential, &key)?;
+            }
+
+            Ok(Some(UnlockedProfile {
+                profile,
+                key,
+                replaced_key,
+            }))
+        }
+        StoredPin::Legacy(stored_hash) => {
+            let legacy_key = derive_legacy_key(pin);
+            if !constant_time_eq(&stored_hash, &legacy_key) {
+                return Ok(None);
+            }
+
+            let profile = read_profile(user_dir, &legacy_key)?;
+            log::info!(
+                "[User] Migrating legacy PIN hash to Argon2id for user {}",
+                profile.id
+            );
+            let key = generate_profile_key();
+            write_credentials(user_dir, pin, &profile, &key)?;
+            Ok(Some(UnlockedProfile {
+                profile,
+                key,
+                replaced_key: Some(legacy_key),
+            }))
+        }
+    }
+}
+
+/// Failed attempts allowed before any delay is enforced
+const LOCKOUT_FREE_ATTEMPTS: u32 = 3;
+/// Lockout applied on the first failure past the free attempts, doubled on each further failure
+const LOCKOUT_BASE_SECONDS: i64 = 30;
+/// Upper bound for a single lockout period
+const LOCKOUT_MAX_SECONDS: i64 = 60 * 60;
+
+/// Serializes PIN checks so parallel calls cannot slip past the lockout
+static PIN_CHECK_LOCK: Mutex<()> = Mutex::new(());
+
+/// Failed-attempt state persisted in lockout.json
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct LockoutState {
+    failed_attempts: u32,
+    /// RFC 3339 timestamp before which no PIN attempt is accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked_until: Option<String>,
+}
+
+impl LockoutState {
+    fn load(user_dir: &Path) -> Self {
+        fs::read_to_string(user_dir.join("lockout.json"))
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, user_dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_atomic(&user_dir.join("lockout.json"), json.as_bytes())
+    }
+
+    /// The end of the current lockout, if one is still running
+    fn active_until(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        let until = chrono::DateTime::parse_from_rfc3339(self.locked_until.as_deref()?).ok()?;
+        (until > chrono::Local::now()).then_some(until)
+    }
+
+    /// Record a wrong PIN and start a lockout once the free attempts are used up
+    fn register_failure(&mut self) {
+        self.failed_attempts += 1;
+        if self.failed_attempts > LOCKOUT_FREE_ATTEMPTS {
+            let exponent = (self.failed_attempts - LOCKOUT_FREE_ATTEMPTS - 1).min(16);
+            let seconds = (LOCKOUT_BASE_SECONDS << exponent).min(LOCKOUT_MAX_SECONDS);
+            let until = chrono::Local::now() + chrono::Duration::seconds(seconds);
+            self.locked_until = Some(until.to_rfc3339());
+        }
+    }
+}
+
+/// Build the error returned while a profile is locked out
+fn lockout_error(until: chrono::DateTime<chrono::FixedOffset>) -> String {
+    let remaining = until
+        .signed_duration_since(chrono::Local::now())
+        .num_seconds()
+        .max(1);
+    format!(
+        "Too many failed PIN attempts. Retry after {} ({} seconds)",
+        until.to_rfc3339(),
+        remaining
+    )
+}
+
+/// Check a PIN with brute-force protection: failures are counted in lockout.json,
+/// and past a few attempts every further failure doubles a temporary lockout.
+/// A successful unlock resets the counter.
+fn unlock_profile(user_dir: &Path, pin: &str) -> Result<UnlockedProfile, String> {
+    let _guard = PIN_CHECK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut lockout = LockoutState::load(user_dir);
+    if let Some(until) = lockout.active_until() {
+        log::warn!("[User] PIN attempt rejected, locked out until {}", until);
+        return Err(lockout_error(until));
+    }
+
+    match check_pin(user_dir, pin)? {
+        Some(unlocked) => {
+            if lockout.failed_attempts > 0 {
+                LockoutState::default().save(user_dir)?;
+            }
+            Ok(unlocked)
+        }
+        None => {
+            lockout.register_failure();
+            lockout.save(user_dir)?;
+            log::warn!(
+                "[User] Invalid PIN ({} consecutive failure(s))",
+                lockout.failed_attempts
+            );
+            match lockout.active_until() {
+                Some(until) => Err(lockout_error(until)),
+                None => Err("Invalid PIN".to_string()),
+            }
+        }
+    }
+}
+
+/// Unlock a user by PIN. When the unlock rewrote the credentials (legacy
+/// migration, parameter upgrade), encrypted vault files follow the new key.
+fn unlock_user(app: &AppHandle, user_id: &str, pin: &str) -> Result<UnlockedProfile, String> {
+    let user_dir = get_user_dir(app, user_id)?;
+    if !user_dir.exists() {
+        return Err("User not found".to_string());
+    }
+
+    let unlocked = unlock_profile(&user_dir, pin)?;
+
+    if let Some(old_key) = unlocked.replaced_key {
+        if let Err(e) = crate::vault::rekey(app, user_id, &old_key, &unlocked.key) {
+            log::error!("[User] Failed to re-key vault for user {}: {}", user_id, e);
+        }
+    }
+
+    Ok(unlocked)
+}
+
 /// Get the users directory path (next to logs)
 fn get_users_dir(app: &AppHandle) -> Result<PathBuf, String> {
     let app_local_data = app
@@ -81,43 +682,99 @@ fn get_user_dir(app: &AppHandle, user_id: &str) -> Result<PathBuf, String> {
     Ok(get_users_dir(app)?.join(user_id))
 }
 
-#[tauri::command]
-pub fn check_user_exists(app: AppHandle) -> Result<Option<ExistingUser>, String> {
-    let users_dir = get_users_dir(&app)?;
+/// Path of the marker file remembering which profile logged in last
+fn get_last_user_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(get_users_dir(app)?.join("last_user"))
+}
+
+/// Read the unencrypted discovery metadata (id, username, credential kind) of a user directory
+fn read_user_meta(user_dir: &Path) -> Option<ExistingUser> {
+    let meta_raw = fs::read_to_string(user_dir.join("meta.json")).ok()?;
+    let meta = serde_json::from_str::<serde_json::Value>(&meta_raw).ok()?;
+    Some(ExistingUser {
+        id: meta["id"].as_str()?.to_string(),
+        username: meta["username"].as_str()?.to_string(),
+        credential: serde_json::from_value(meta["credential"].clone()).unwrap_or_default(),
+    })
+}
+
+/// Write the unencrypted discovery metadata shown on the login screen
+fn write_user_meta(user_dir: &Path, profile: &UserProfile) -> Result<(), String> {
+    let meta = ExistingUser {
+        id: profile.id.clone(),
+        username: profile.username.clone(),
+        credential: profile.credential.clone(),
+    };
+    let json = serde_json::to_string_pretty(&meta).map_err(|e| e.to_string())?;
+    fs::write(user_dir.join("meta.json"), json).map_err(|e| e.to_string())
+}
+
+/// Collect every profile found under the users directory, sorted by username
+fn collect_users(app: &AppHandle) -> Result<Vec<ExistingUser>, String> {
+    let users_dir = get_users_dir(app)?;
 
     if !users_dir.exists() {
-        return Ok(None);
-    }
-
-    // Look for the first user directory (UUID-named)
-    let entries = fs::read_dir(&users_dir).map_err(|e| e.to_string())?;
-
-    for entry in entries {
-        let entry = entry.map_err(|e| e.to_string())?;
-        if entry.path().is_dir() {
-            // Try to read the username from the unencrypted metadata file
-            let meta_path = entry.path().join("meta.json");
-            if meta_path.exists() {
-                let meta_raw = fs::read_to_string(&meta_path).map_err(|e| e.to_string())?;
-                if let Ok(meta) = serde_json::from_str::<serde_json::Value>(&meta_raw) {
-                    if let (Some(id), Some(username)) =
-                        (meta["id"].as_str(), meta["username"].as_str())
-                    {
-                        return Ok(Some(ExistingUser {
-                            id: id.to_string(),
-                            username: username.to_string(),
-                        }));
-                    }
-                }
+        return Ok(vec![]);
+    }
+
+    let mut users: Vec<ExistingUser> = fs::read_dir(&users_dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|e| e.ok())
+        // Dot folders are imports being staged (or left by a crash), not profiles
+        .filter(|e| e.path().is_dir() && !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| read_user_meta(&e.path()))
+        .collect();
+
+    users.sort_by_key(|u| u.username.to_lowercase());
+    Ok(users)
+}
+
+/// Remember the profile that just logged in, so it is preselected on next launch
+fn remember_last_user(app: &AppHandle, user_id: &str) {
+    match get_last_user_path(app) {
+        Ok(path) => {
+            if let Err(e) = fs::write(&path, user_id) {
+                log::warn!("[User] Failed to remember last user: {}", e);
             }
         }
+        Err(e) => log::warn!("[User] Failed to remember last user: {}", e),
     }
+}
 
-    Ok(None)
+/// Return the last profile that logged in, or the first one if none is recorded
+#[tauri::command]
+pub fn check_user_exists(app: AppHandle) -> Result<Option<ExistingUser>, String> {
+    let users = collect_users(&app)?;
+
+    let last_user_id = get_last_user_path(&app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string());
+
+    if let Some(last_id) = last_user_id {
+        if let Some(user) = users.iter().find(|u| u.id == last_id) {
+            return Ok(Some(user.clone()));
+        }
+    }
+
+    Ok(users.into_iter().next())
 }
 
+/// List every profile on this machine (household profiles)
 #[tauri::command]
-pub fn create_user(app: AppHandle, username: String, pin: String) -> Result<UserProfile, String> {
+pub fn list_users(app: AppHandle) -> Result<Vec<ExistingUser>, String> {
+    let users = collect_users(&app)?;
+    log::info!("[User] Found {} profile(s)", users.len());
+    Ok(users)
+}
+
+#[tauri::command]
+pub fn create_user(
+    app: AppHandle,
+    username: String,
+    pin: String,
+    credential: Option<CredentialPolicy>,
+) -> Result<CreatedUser, String> {
     // Validate username: no spaces, no special chars except - and _
     if username.is_empty() {
         return Err("Username cannot be empty".to_string());
@@ -130,10 +787,9 @@ pub fn create_user(app: AppHandle, username: String, pin: String) -> Result<User
         return Err("Username can only contain letters, numbers, hyphens and underscores".to_string());
     }
 
-    // Validate PIN: exactly 4 digits
-    if pin.len() != 4 || !pin.chars().all(|c| c.is_ascii_digit()) {
-        return Err("PIN must be exactly 4 digits".to_string());
-    }
+    // Validate the PIN or passphrase against the chosen policy (4-digit PIN by default)
+    let credential = credential.unwrap_or_default();
+    credential.validate(&pin)?;
 
     let user_id = Uuid::new_v4().to_string();
     let user_dir = get_user_dir(&app, &user_id)?;
@@ -142,13 +798,12 @@ pub fn create_user(app: AppHandle, username: String, pin: String) -> Result<User
         return Err("User already exists".to_string());
     }
 
-    // Check no user exists yet (single-user app)
-    let users_dir = get_users_dir(&app)?;
-    if users_dir.exists() {
-        let entries = fs::read_dir(&users_dir).map_err(|e| e.to_string())?;
-        if entries.filter_map(|e| e.ok()).any(|e| e.path().is_dir()) {
-            return Err("User already exists".to_string());
-        }
+    // Usernames must stay unique across the household's profiles
+    if collect_users(&app)?
+        .iter()
+        .any(|u| u.username.eq_ignore_ascii_case(&username))
+    {
+        return Err("A profile with this username already exists".to_string());
     }
 
     // Create directories
@@ -158,58 +813,52 @@ pub fn create_user(app: AppHandle, username: String, pin: String) -> Result<User
         id: user_id.clone(),
         username: username.clone(),
         created_at: chrono::Local::now().to_rfc3339(),
+        credential,
     };
 
-    // Serialize and encrypt the profile
-    let profile_json = serde_json::to_string(&profile).map_err(|e| e.to_string())?;
-    let encrypted = encrypt(profile_json.as_bytes(), &pin)?;
-
-    // Write the encrypted profile
-    let profile_path = user_dir.join("profile.enc");
-    fs::write(&profile_path, &encrypted).map_err(|e| e.to_string())?;
-
-    // Write unencrypted metadata (id + username) for discovery
-    let meta = serde_json::json!({ "id": user_id, "username": username });
-    let meta_path = user_dir.join("meta.json");
-    fs::write(&meta_path, serde_json::to_string_pretty(&meta).unwrap()).map_err(|e| e.to_string())?;
-
-    // Store a PIN hash for verification (not the PIN itself)
-    let pin_hash = derive_key(&pin);
-    let hash_path = user_dir.join("pin.hash");
-    fs::write(&hash_path, &pin_hash).map_err(|e| e.to_string())?;
+    // Random profile key, wrapped by the PIN (pin.hash) and by a recovery code
+    let key = generate_profile_key();
+    write_credentials(&user_dir, &pin, &profile, &key)?;
+    let recovery_code = write_recovery(&user_dir, &key)?;
+
+    // Write unencrypted metadata (id, username, credential kind) for discovery
+    write_user_meta(&user_dir, &profile)?;
+
+    // The new profile is logged in right away
+    remember_last_user(&app, &user_id);
+    crate::vault::unlock(&app, &user_id, &key);
+    crate::session::open(
+        &app,
+        &user_id,
+        crate::media::Settings::default().auto_lock_minutes,
+    );
 
     log::info!("[User] Created user: {} ({})", username, user_id);
 
-    Ok(profile)
+    Ok(CreatedUser {
+        profile,
+        recovery_code,
+    })
 }
 
 #[tauri::command]
 pub fn verify_pin(app: AppHandle, user_id: String, pin: String) -> Result<UserProfile, String> {
-    let user_dir = get_user_dir(&app, &user_id)?;
+    // Verify PIN and decrypt profile (upgrades legacy credentials on success)
+    let unlocked = unlock_user(&app, &user_id, &pin)?;
+    remember_last_user(&app, &user_id);
 
-    if !user_dir.exists() {
-        return Err("User not found".to_string());
-    }
-
-    // Verify PIN hash
-    let hash_path = user_dir.join("pin.hash");
-    let stored_hash = fs::read(&hash_path).map_err(|e| e.to_string())?;
-    let provided_hash = derive_key(&pin);
-
-    if stored_hash != provided_hash {
-        return Err("Invalid PIN".to_string());
-    }
+    // Keep the vault key in memory so settings, library and metadata can be read
+    crate::vault::unlock(&app, &user_id, &unlocked.key);
 
-    // Decrypt and return profile
-    let profile_path = user_dir.join("profile.enc");
-    let encrypted = fs::read(&profile_path).map_err(|e| e.to_string())?;
-    let decrypted = decrypt(&encrypted, &pin)?;
-    let profile: UserProfile =
-        serde_json::from_slice(&decrypted).map_err(|e| e.to_string())?;
+    // Open the session that authorizes the media, watcher and player commands
+    let auto_lock_minutes = crate::media::load_settings(&app, &user_id)
+        .map(|s| s.auto_lock_minutes)
+        .unwrap_or(30);
+    crate::session::open(&app, &user_id, auto_lock_minutes);
 
     log::info!("[User] PIN verified for user: {}", user_id);
 
-    Ok(profile)
+    Ok(unlocked.profile)
 }
 
 /// Update the username for a user (requires current PIN for re-encryption)
@@ -234,31 +883,29 @@ pub fn update_username(
     }
 
     // Verify PIN first
-    let _profile = verify_pin(app.clone(), user_id.clone(), pin.clone())?;
-
+    let unlocked = unlock_user(&app, &user_id, &pin)?;
     let user_dir = get_user_dir(&app, &user_id)?;
 
+    if collect_users(&app)?
+        .iter()
+        .any(|u| u.id != user_id && u.username.eq_ignore_ascii_case(&new_username))
+    {
+        return Err("A profile with this username already exists".to_string());
+    }
+
     // Build updated profile
     let updated = UserProfile {
         id: user_id.clone(),
         username: new_username.clone(),
-        created_at: _profile.created_at,
+        created_at: unlocked.profile.created_at,
+        credential: unlocked.profile.credential,
     };
 
-    // Re-encrypt profile with the same PIN
-    let profile_json = serde_json::to_string(&updated).map_err(|e| e.to_string())?;
-    let encrypted = encrypt(profile_json.as_bytes(), &pin)?;
-    let profile_path = user_dir.join("profile.enc");
-    fs::write(&profile_path, &encrypted).map_err(|e| e.to_string())?;
+    // Re-encrypt profile with the same key
+    write_profile(&user_dir, &updated, &unlocked.key)?;
 
     // Update meta.json
-    let meta = serde_json::json!({ "id": user_id, "username": new_username });
-    let meta_path = user_dir.join("meta.json");
-    fs::write(
-        &meta_path,
-        serde_json::to_string_pretty(&meta).unwrap(),
-    )
-    .map_err(|e| e.to_string())?;
+    write_user_meta(&user_dir, &updated)?;
 
     log::info!(
         "[User] Username updated to '{}' for user {}",
@@ -269,43 +916,777 @@ pub fn update_username(
     Ok(updated)
 }
 
-/// Update the PIN for a user
+/// Update the PIN for a user. Passing `credential` also switches the kind of
+/// secret (N-digit PIN, passphrase or none); otherwise the current kind is kept.
 #[tauri::command]
 pub fn update_pin(
     app: AppHandle,
     user_id: String,
     current_pin: String,
     new_pin: String,
+    credential: Option<CredentialPolicy>,
 ) -> Result<(), String> {
-    // Validate new PIN
-    if new_pin.len() != 4 || !new_pin.chars().all(|c| c.is_ascii_digit()) {
-        return Err("PIN must be exactly 4 digits".to_string());
+    // Verify current PIN
+    let unlocked = unlock_user(&app, &user_id, &current_pin)?;
+    let user_dir = get_user_dir(&app, &user_id)?;
+
+    // Validate the new PIN against the (possibly new) policy
+    let credential = credential.unwrap_or_else(|| unlocked.profile.credential.clone());
+    credential.validate(&new_pin)?;
+
+    // New salt and PIN record wrapping the same profile key: profile.enc,
+    // the vault and the recovery code stay valid
+    write_pin_record(&user_dir, &new_pin, &credential, &unlocked.key)?;
+
+    if credential != unlocked.profile.credential {
+        let updated = UserProfile {
+            credential,
+            ..unlocked.profile
+        };
+        write_profile(&user_dir, &updated, &unlocked.key)?;
+        write_user_meta(&user_dir, &updated)?;
+        log::info!(
+            "[User] Credential type changed to {:?} for user {}",
+            updated.credential,
+            user_id
+        );
     }
 
-    // Verify current PIN
-    let profile = verify_pin(app.clone(), user_id.clone(), current_pin)?;
+    log::info!("[User] PIN updated for user {}", user_id);
 
+    Ok(())
+}
+
+/// Set a new PIN with the recovery code, for when the PIN is forgotten.
+/// This bypasses (and clears) the PIN lockout; the recovery code stays valid.
+#[tauri::command]
+pub fn reset_pin_with_recovery(
+    app: AppHandle,
+    user_id: String,
+    recovery_code: String,
+    new_pin: String,
+) -> Result<(), String> {
     let user_dir = get_user_dir(&app, &user_id)?;
+    if !user_dir.exists() {
+        return Err("User not found".to_string());
+    }
 
-    // Re-encrypt profile with new PIN
-    let profile_json = serde_json::to_string(&profile).map_err(|e| e.to_string())?;
-    let encrypted = encrypt(profile_json.as_bytes(), &new_pin)?;
-    let profile_path = user_dir.join("profile.enc");
-    fs::write(&profile_path, &encrypted).map_err(|e| e.to_string())?;
+    reset_pin(&user_dir, &recovery_code, &new_pin)?;
 
-    // Update PIN hash
-    let pin_hash = derive_key(&new_pin);
-    let hash_path = user_dir.join("pin.hash");
-    fs::write(&hash_path, &pin_hash).map_err(|e| e.to_string())?;
+    log::info!("[User] PIN reset with recovery code for user {}", user_id);
+    Ok(())
+}
 
-    log::info!("[User] PIN updated for user {}", user_id);
+/// Replace the PIN of the profile in `user_dir` using its recovery code.
+/// The profile key is kept, so profile.enc and the vault stay as they are.
+fn reset_pin(user_dir: &Path, recovery_code: &str, new_pin: &str) -> Result<(), String> {
+    let _guard = PIN_CHECK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
 
+    let key = match unwrap_with_recovery(user_dir, recovery_code)? {
+        Some(key) => key,
+        None => {
+            log::warn!("[User] Invalid recovery code");
+            return Err("Invalid recovery code".to_string());
+        }
+    };
+
+    // Make sure the key really opens this profile before replacing the PIN
+    let profile = read_profile(user_dir, &key)?;
+    profile.credential.validate(new_pin)?;
+    write_pin_record(user_dir, new_pin, &profile.credential, &key)?;
+    LockoutState::default().save(user_dir)
+}
+
+/// Replace the recovery code of a profile (requires its PIN).
+/// Returns the new code, which is not stored anywhere in readable form.
+#[tauri::command]
+pub fn regenerate_recovery_code(
+    app: AppHandle,
+    user_id: String,
+    pin: String,
+) -> Result<String, String> {
+    crate::session::require(&app, &user_id)?;
+    let unlocked = unlock_user(&app, &user_id, &pin)?;
+    let user_dir = get_user_dir(&app, &user_id)?;
+
+    let code = write_recovery(&user_dir, &unlocked.key)?;
+
+    log::info!("[User] Recovery code regenerated for user {}", user_id);
+    Ok(code)
+}
+
+/// Whether a profile has a recovery code (older profiles need to generate one)
+#[tauri::command]
+pub fn has_recovery_code(app: AppHandle, user_id: String) -> Result<bool, String> {
+    Ok(get_user_dir(&app, &user_id)?
+        .join("recovery.json")
+        .is_file())
+}
+
+/// End the session of a user: stops their watcher and locks the vault
+#[tauri::command]
+pub fn logout(app: AppHandle, user_id: String) -> Result<(), String> {
+    crate::session::close(&app, &user_id, "logout");
+    Ok(())
+}
+
+/// Get the active session (None when no profile is unlocked)
+#[tauri::command]
+pub fn get_session(app: AppHandle) -> Result<Option<crate::session::SessionInfo>, String> {
+    Ok(crate::session::current(&app))
+}
+
+/// Get the vault configuration (whether settings, library and metadata are encrypted)
+#[tauri::command]
+pub fn get_vault_mode(
+    app: AppHandle,
+    user_id: String,
+) -> Result<crate::vault::VaultConfig, String> {
+    crate::vault::load_config(&app, &user_id)
+}
+
+/// Enable or disable the encrypted vault, migrating existing files (requires PIN)
+#[tauri::command]
+pub fn set_vault_mode(
+    app: AppHandle,
+    user_id: String,
+    pin: String,
+    enabled: bool,
+) -> Result<crate::vault::VaultConfig, String> {
+    let unlocked = unlock_user(&app, &user_id, &pin)?;
+    crate::vault::set_enabled(&app, &user_id, &unlocked.key, enabled)?;
+    crate::vault::load_config(&app, &user_id)
+}
+
+/// Overwrite every file under `dir` with random bytes, so the encrypted blobs
+/// cannot be recovered from the freed space. Best effort: SSD wear levelling and
+/// copy-on-write filesystems may still keep old blocks around.
+fn overwrite_files(dir: &Path) -> Result<usize, String> {
+    use std::io::Write;
+
+    let mut overwritten = 0;
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            overwritten += overwrite_files(&path)?;
+            continue;
+        }
+
+        let len = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .map_err(|e| e.to_string())?;
+        let mut chunk = vec![0u8; 64 * 1024];
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(chunk.len() as u64) as usize;
+            rand::thread_rng().fill_bytes(&mut chunk[..n]);
+            file.write_all(&chunk[..n]).map_err(|e| e.to_string())?;
+            remaining -= n as u64;
+        }
+        file.sync_all().map_err(|e| e.to_string())?;
+        overwritten += 1;
+    }
+    Ok(overwritten)
+}
+
+/// Delete a profile and everything stored in its directory (requires its PIN).
+/// With `secure_erase`, every file is overwritten before being removed.
+#[tauri::command]
+pub fn delete_user(
+    app: AppHandle,
+    user_id: String,
+    pin: String,
+    secure_erase: bool,
+) -> Result<(), String> {
+    // Confirm ownership before removing anything
+    unlock_user(&app, &user_id, &pin)?;
+    let user_dir = get_user_dir(&app, &user_id)?;
+    let was_logged_in = crate::session::current(&app).is_some_and(|s| s.user_id == user_id);
+
+    // Make sure no background thread keeps writing into the directory
+    crate::session::close(&app, &user_id, "deleted");
+    if let Err(e) = crate::watcher::stop_watching(&app, &user_id) {
+        log::warn!("[User] Failed to stop watcher before deleting user: {}", e);
+    }
+    if let Err(e) = crate::metadata::stop_metadata_retry(&app, &user_id) {
+        log::warn!(
+            "[User] Failed to stop metadata retry before deleting user: {}",
+            e
+        );
+    }
+    crate::db::close(&app, &user_id);
+
+    // The peer endpoint runs on behalf of the logged-in profile
+    if was_logged_in {
+        tauri::async_runtime::block_on(crate::peer::reset(&app));
+    }
+
+    if secure_erase {
+        let overwritten = overwrite_files(&user_dir)?;
+        log::info!(
+            "[User] Overwrote {} file(s) of user {}",
+            overwritten,
+            user_id
+        );
+    }
+    fs::remove_dir_all(&user_dir).map_err(|e| e.to_string())?;
+    crate::vault::lock(&app, &user_id);
+
+    // Forget it as the last used profile
+    let last_user_path = get_last_user_path(&app)?;
+    if fs::read_to_string(&last_user_path)
+        .map(|id| id.trim() == user_id)
+        .unwrap_or(false)
+    {
+        let _ = fs::remove_file(&last_user_path);
+    }
+
+    log::info!("[User] Deleted user {}", user_id);
     Ok(())
 }
 
+/// Header of an exported account bundle
+const BUNDLE_MAGIC: &[u8] = b"PHACCT01";
+/// Current version of the bundle format
+const BUNDLE_VERSION: u32 = 1;
+/// Name of the manifest entry inside a bundle
+const BUNDLE_MANIFEST: &str = "bundle.json";
+/// Highest Argon2id costs accepted from a bundle header, as a multiple of those
+/// of new records, so that a crafted bundle cannot exhaust memory or CPU
+const BUNDLE_MAX_COST_FACTOR: u32 = 2;
+/// Minimum length of the passphrase protecting a bundle
+const BUNDLE_MIN_PASSPHRASE_LEN: usize = 8;
+
+/// A file inside a bundle: path relative to the user directory and its contents
+type BundleEntry = (String, Vec<u8>);
+
+/// Unencrypted bundle header: how to derive the key from the passphrase
+#[derive(Serialize, Deserialize)]
+struct BundleHeader {
+    version: u32,
+    kdf: String,
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// Description of an exported account, stored encrypted inside the bundle
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BundleInfo {
+    pub user_id: String,
+    pub username: String,
+    pub exported_at: String,
+    /// Media folders of the exported profile, to build the import folder mapping
+    pub folders: Vec<String>,
+    pub file_count: usize,
+}
+
+/// Collect the files of a user directory to export, as (relative path, absolute path).
+/// Lockout state and leftover temporary files stay on the machine.
+fn collect_bundle_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = format!("{}{}", prefix, name);
+
+        if path.is_dir() {
+            collect_bundle_files(&path, &format!("{}/", relative), files)?;
+        } else if name != "lockout.json"
+            && !name.ends_with(".tmp")
+            && !crate::db::is_database_file(&name)
+        {
+            files.push((relative, path));
+        }
+    }
+    Ok(())
+}
+
+/// Serialize bundle entries as a flat sequence of (path length, path, data length, data)
+fn pack_bundle_entries(entries: &[BundleEntry]) -> Vec<u8> {
+    let mut packed = Vec::new();
+    for (path, data) in entries {
+        packed.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        packed.extend_from_slice(path.as_bytes());
+        packed.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        packed.extend_from_slice(data);
+    }
+    packed
+}
+
+/// Parse packed bundle entries, rejecting paths that would escape the user directory
+fn unpack_bundle_entries(packed: &[u8]) -> Result<Vec<BundleEntry>, String> {
+    fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+        let end = pos
+            .checked_add(len)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| "Truncated account bundle".to_string())?;
+        let slice = &data[*pos..end];
+        *pos = end;
+        Ok(slice)
+    }
+
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while pos < packed.len() {
+        let path_len = u32::from_le_bytes(take(packed, &mut pos, 4)?.try_into().unwrap()) as usize;
+        let path = String::from_utf8(take(packed, &mut pos, path_len)?.to_vec())
+            .map_err(|_| "Invalid path in account bundle".to_string())?;
+        let data_len = u64::from_le_bytes(take(packed, &mut pos, 8)?.try_into().unwrap()) as usize;
+        let data = take(packed, &mut pos, data_len)?.to_vec();
+
+        if path.is_empty()
+            || path.starts_with('/')
+            || path.contains('\\')
+            || path.contains(':')
+            || path
+                .split('/')
+                .any(|part| part.is_empty() || part == "." || part == "..")
+        {
+            return Err(format!("Invalid path in account bundle: {}", path));
+        }
+        entries.push((path, data));
+    }
+    Ok(entries)
+}
+
+/// Derive the bundle encryption key from the passphrase
+fn derive_bundle_key(passphrase: &str, header: &BundleHeader) -> Result<[u8; 32], String> {
+    if header.kdf != "argon2id" {
+        return Err(format!(
+            "Unsupported key derivation function: {}",
+            header.kdf
+        ));
+    }
+    if header.m_cost > KDF_MEMORY_KIB * BUNDLE_MAX_COST_FACTOR
+        || header.t_cost > KDF_ITERATIONS * BUNDLE_MAX_COST_FACTOR
+        || header.p_cost > KDF_PARALLELISM * BUNDLE_MAX_COST_FACTOR
+    {
+        return Err("Unsupported key derivation costs in account bundle".to_string());
+    }
+    let salt = BASE64.decode(&header.salt).map_err(|e| e.to_string())?;
+    Ok(derive_keys(
+        passphrase,
+        &salt,
+        header.m_cost,
+        header.t_cost,
+        header.p_cost,
+    )?
+    .encrypt)
+}
+
+/// Read and decrypt a bundle file, returning its manifest and entries
+fn read_bundle(source: &Path, passphrase: &str) -> Result<(BundleInfo, Vec<BundleEntry>), String> {
+    let raw = fs::read(source).map_err(|e| e.to_string())?;
+    if !raw.starts_with(BUNDLE_MAGIC) || raw.len() < BUNDLE_MAGIC.len() + 4 {
+        return Err("Not a Popcorn Hero account bundle".to_string());
+    }
+
+    let mut pos = BUNDLE_MAGIC.len();
+    let header_len = u32::from_le_bytes(raw[pos..pos + 4].try_into().unwrap()) as usize;
+    pos += 4;
+    let header_raw = raw
+        .get(pos..pos + header_len)
+        .ok_or_else(|| "Truncated account bundle".to_string())?;
+    let header: BundleHeader = serde_json::from_slice(header_raw).map_err(|e| e.to_string())?;
+    if header.version > BUNDLE_VERSION {
+        return Err("This bundle was made by a newer version of Popcorn Hero".to_string());
+    }
+
+    let key = derive_bundle_key(passphrase, &header)?;
+    let packed = decrypt(&raw[pos + header_len..], &key)
+        .map_err(|_| "Wrong passphrase or corrupted bundle".to_string())?;
+    let mut entries = unpack_bundle_entries(&packed)?;
+
+    let manifest_index = entries
+        .iter()
+        .position(|(path, _)| path == BUNDLE_MANIFEST)
+        .ok_or_else(|| "Account bundle has no manifest".to_string())?;
+    let (_, manifest_raw) = entries.remove(manifest_index);
+    let info: BundleInfo = serde_json::from_slice(&manifest_raw).map_err(|e| e.to_string())?;
+
+    if Uuid::parse_str(&info.user_id).is_err() {
+        return Err("Invalid user ID in account bundle".to_string());
+    }
+    Ok((info, entries))
+}
+
+/// Normalize a folder mapping (source folder -> folder on this machine),
+/// longest source first so nested folders win over their parents
+fn normalize_folder_mapping(mapping: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = mapping
+        .iter()
+        .map(|(from, to)| {
+            (
+                from.replace('\\', "/").trim_end_matches('/').to_string(),
+                to.replace('\\', "/").trim_end_matches('/').to_string(),
+            )
+        })
+        .filter(|(from, _)| !from.is_empty())
+        .collect();
+    pairs.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+    pairs
+}
+
+/// Rewrite a path through the folder mapping (unchanged if no folder matches)
+fn remap_path(path: &str, mapping: &[(String, String)]) -> String {
+    for (from, to) in mapping {
+        if path == from {
+            return to.clone();
+        }
+        if let Some(rest) = path.strip_prefix(from.as_str()) {
+            if rest.starts_with('/') {
+                return format!("{}{}", to, rest);
+            }
+        }
+    }
+    path.to_string()
+}
+
+/// Rewrite the paths of a JSON array: its strings, or the `key` of its objects
+fn remap_list(
+    list: Option<&mut serde_json::Value>,
+    key: Option<&str>,
+    mapping: &[(String, String)],
+) {
+    let items = match list.and_then(|l| l.as_array_mut()) {
+        Some(items) => items,
+        None => return,
+    };
+    for item in items.iter_mut() {
+        let target = match key {
+            Some(key) => match item.get_mut(key) {
+                Some(target) => target,
+                None => continue,
+            },
+            None => item,
+        };
+        if let Some(path) = target.as_str() {
+            *target = serde_json::Value::String(remap_path(path, mapping));
+        }
+    }
+}
+
+/// Rewrite the absolute paths stored in an exported JSON file (settings folders,
+/// library entries, metadata file paths, scan cache keys). Entry IDs are kept, so
+/// metadata stays attached, and the scan cache stays valid for a drive mounted
+/// elsewhere.
+fn remap_bundle_file(
+    relative: &str,
+    data: Vec<u8>,
+    mapping: &[(String, String)],
+) -> Result<Vec<u8>, String> {
+    let is_meta = relative.starts_with("metas/") && relative.ends_with("/meta.json");
+    let is_known = matches!(
+        relative,
+        "settings.json" | "library.json" | "scan_cache.json"
+    );
+    if mapping.is_empty() || !(is_known || is_meta) {
+        return Ok(data);
+    }
+
+    let mut value: serde_json::Value = serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+    let remap = |v: &mut serde_json::Value, key: &str| {
+        if let Some(path) = v.get(key).and_then(|p| p.as_str()) {
+            v[key] = serde_json::Value::String(remap_path(path, mapping));
+        }
+    };
+
+    if relative == "settings.json" {
+        if let Some(folders) = value
+            .get_mut("media")
+            .and_then(|m| m.get_mut("folders"))
+            .and_then(|f| f.as_array_mut())
+        {
+            for folder in folders.iter_mut() {
+                // Folders are either plain paths or objects with a "path"
+                if let Some(path) = folder.as_str() {
+                    *folder = serde_json::Value::String(remap_path(path, mapping));
+                } else {
+                    remap(folder, "path");
+                }
+            }
+        }
+    } else if relative == "library.json" {
+        if let Some(entries) = value.as_array_mut() {
+            for entry in entries.iter_mut() {
+                remap(entry, "path");
+                remap_list(entry.get_mut("parts"), None, mapping);
+                remap_list(entry.get_mut("subtitles"), Some("path"), mapping);
+                remap_list(
+                    entry
+                        .get_mut("disc")
+                        .and_then(|d| d.get_mut("main_feature")),
+                    None,
+                    mapping,
+                );
+            }
+        }
+    } else if relative == "scan_cache.json" {
+        // Directories and files are keyed by path
+        for section in ["dirs", "files"] {
+            if let Some(states) = value.get_mut(section).and_then(|s| s.as_object_mut()) {
+                *states = std::mem::take(states)
+                    .into_iter()
+                    .map(|(path, state)| (remap_path(&path, mapping), state))
+                    .collect();
+            }
+        }
+    } else {
+        remap(&mut value, "file_path");
+    }
+
+    serde_json::to_vec_pretty(&value).map_err(|e| e.to_string())
+}
+
+/// Export a profile (credentials, settings, library, metadata with images and avatar)
+/// as a single file encrypted with a passphrase. Vault files and the library database
+/// (as JSON files) are exported decrypted inside the bundle and sealed again on import.
+#[tauri::command]
+pub fn export_account(
+    app: AppHandle,
+    user_id: String,
+    pin: String,
+    passphrase: String,
+    destination: String,
+) -> Result<BundleInfo, String> {
+    crate::session::require(&app, &user_id)?;
+    if passphrase.chars().count() < BUNDLE_MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "Passphrase must be at least {} characters",
+            BUNDLE_MIN_PASSPHRASE_LEN
+        ));
+    }
+
+    let unlocked = unlock_user(&app, &user_id, &pin)?;
+    let user_dir = get_user_dir(&app, &user_id)?;
+
+    let mut files = Vec::new();
+    collect_bundle_files(&user_dir, "", &mut files)?;
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut entries = Vec::with_capacity(files.len() + 1);
+    for (relative, path) in files {
+        entries.push((relative, crate::vault::read(&app, &user_id, &path)?));
+    }
+    // The library database goes as the JSON files it is imported from
+    entries.extend(crate::db::export_json(&app, &user_id)?);
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let info = BundleInfo {
+        user_id: user_id.clone(),
+        username: unlocked.profile.username,
+        exported_at: chrono::Local::now().to_rfc3339(),
+        folders: crate::media::load_settings(&app, &user_id)?
+            .media
+            .folders
+            .into_iter()
+            .map(|f| f.path)
+            .collect(),
+        file_count: entries.len(),
+    };
+    let manifest = serde_json::to_vec_pretty(&info).map_err(|e| e.to_string())?;
+    entries.push((BUNDLE_MANIFEST.to_string(), manifest));
+
+    let salt = generate_salt();
+    let header = BundleHeader {
+        version: BUNDLE_VERSION,
+        kdf: "argon2id".to_string(),
+        salt: BASE64.encode(salt),
+        m_cost: KDF_MEMORY_KIB,
+        t_cost: KDF_ITERATIONS,
+        p_cost: KDF_PARALLELISM,
+    };
+    let key = derive_bundle_key(&passphrase, &header)?;
+    let header_json = serde_json::to_vec(&header).map_err(|e| e.to_string())?;
+
+    let mut bundle = BUNDLE_MAGIC.to_vec();
+    bundle.extend_from_slice(&(header_json.len() as u32).to_le_bytes());
+    bundle.extend_from_slice(&header_json);
+    bundle.extend_from_slice(&encrypt(&pack_bundle_entries(&entries), &key)?);
+    write_atomic(Path::new(&destination), &bundle)?;
+
+    log::info!(
+        "[User] Exported user {} ({} file(s)) to {}",
+        user_id,
+        info.file_count,
+        destination
+    );
+    Ok(info)
+}
+
+/// Read the manifest of a bundle (to show its media folders before importing)
+#[tauri::command]
+pub fn inspect_account_bundle(source: String, passphrase: String) -> Result<BundleInfo, String> {
+    let (info, _) = read_bundle(Path::new(&source), &passphrase)?;
+    Ok(info)
+}
+
+/// Import a profile from an exported bundle. Library and settings paths are rewritten
+/// through `folder_mapping` (exported folder -> folder on this machine). The profile's
+/// PIN is required to check the bundle's credentials and to re-seal its vault.
+#[tauri::command]
+pub fn import_account(
+    app: AppHandle,
+    source: String,
+    passphrase: String,
+    pin: String,
+    folder_mapping: HashMap<String, String>,
+) -> Result<ExistingUser, String> {
+    let (info, entries) = read_bundle(Path::new(&source), &passphrase)?;
+
+    let users = collect_users(&app)?;
+    if users.iter().any(|u| u.id == info.user_id) {
+        return Err("This profile already exists on this machine".to_string());
+    }
+    if users
+        .iter()
+        .any(|u| u.username.eq_ignore_ascii_case(&info.username))
+    {
+        return Err("A profile with this username already exists".to_string());
+    }
+
+    let user_dir = get_user_dir(&app, &info.user_id)?;
+    if user_dir.exists() {
+        return Err("User already exists".to_string());
+    }
+
+    // Unpack next to the final directory, so a failed import leaves nothing behind
+    let staging_dir = get_users_dir(&app)?.join(format!(".import-{}", info.user_id));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir).map_err(|e| e.to_string())?;
+    }
+
+    let mapping = normalize_folder_mapping(&folder_mapping);
+    let staged = (|| -> Result<[u8; 32], String> {
+        for (relative, data) in entries {
+            let path = staging_dir.join(&relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let data = remap_bundle_file(&relative, data, &mapping)?;
+            fs::write(&path, data).map_err(|e| e.to_string())?;
+        }
+
+        let unlocked = unlock_profile(&staging_dir, &pin)?;
+        if unlocked.profile.id != info.user_id {
+            return Err("Account bundle does not match its profile".to_string());
+        }
+        let _ = fs::remove_file(staging_dir.join("lockout.json"));
+        Ok(unlocked.key)
+    })();
+
+    let key = match staged {
+        Ok(key) => key,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging_dir);
+            return Err(e);
+        }
+    };
+    fs::rename(&staging_dir, &user_dir).map_err(|e| e.to_string())?;
+
+    // Vault files were exported decrypted: seal them again with this profile's key
+    if crate::vault::load_config(&app, &info.user_id)?.enabled {
+        crate::vault::set_enabled(&app, &info.user_id, &key, true)?;
+        crate::vault::lock(&app, &info.user_id);
+    }
+
+    log::info!(
+        "[User] Imported user {} ({}) with {} folder mapping(s)",
+        info.username,
+        info.user_id,
+        mapping.len()
+    );
+    read_user_meta(&user_dir).ok_or_else(|| "Imported profile has no meta.json".to_string())
+}
+
+/// Largest avatar source file accepted
+const AVATAR_MAX_SOURCE_BYTES: u64 = 20 * 1024 * 1024;
+/// Largest avatar source dimensions accepted (width or height)
+const AVATAR_MAX_DIMENSION: u32 = 8192;
+/// Square sizes generated for every avatar, stored as avatar/<size>.webp
+const AVATAR_SIZES: &[u32] = &[64, 128, 256];
+/// Extensions of avatars saved before they were normalized (copied as-is)
+const LEGACY_AVATAR_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp"];
+
+/// Decode an image after checking its real format from the magic bytes,
+/// crop it to a centered square and write every avatar size as WebP
+fn write_avatar(user_dir: &Path, data: &[u8]) -> Result<(), String> {
+    let format = image::guess_format(data)
+        .map_err(|_| "Unsupported image format. Use JPG, PNG, WebP, GIF or BMP.".to_string())?;
+    if !matches!(
+        format,
+        image::ImageFormat::Jpeg
+            | image::ImageFormat::Png
+            | image::ImageFormat::WebP
+            | image::ImageFormat::Gif
+            | image::ImageFormat::Bmp
+    ) {
+        return Err("Unsupported image format. Use JPG, PNG, WebP, GIF or BMP.".to_string());
+    }
+
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(AVATAR_MAX_DIMENSION);
+    limits.max_image_height = Some(AVATAR_MAX_DIMENSION);
+    let mut reader = image::ImageReader::with_format(std::io::Cursor::new(data), format);
+    reader.limits(limits);
+    let image = reader
+        .decode()
+        .map_err(|e| format!("Invalid image: {}", e))?;
+
+    let side = image.width().min(image.height());
+    let square = image.crop_imm(
+        (image.width() - side) / 2,
+        (image.height() - side) / 2,
+        side,
+        side,
+    );
+
+    let avatar_dir = user_dir.join("avatar");
+    fs::create_dir_all(&avatar_dir).map_err(|e| e.to_string())?;
+    for size in AVATAR_SIZES {
+        let resized = square
+            .resize_exact(*size, *size, image::imageops::FilterType::Lanczos3)
+            .to_rgba8();
+        let mut encoded = Vec::new();
+        resized
+            .write_to(
+                &mut std::io::Cursor::new(&mut encoded),
+                image::ImageFormat::WebP,
+            )
+            .map_err(|e| e.to_string())?;
+        write_atomic(&avatar_dir.join(format!("{}.webp", size)), &encoded)?;
+    }
+    Ok(())
+}
+
+/// Convert an avatar left over from before normalization, then remove the original
+fn migrate_legacy_avatar(user_dir: &Path) {
+    for ext in LEGACY_AVATAR_EXTENSIONS {
+        let legacy = user_dir.join(format!("avatar.{}", ext));
+        if !legacy.is_file() {
+            continue;
+        }
+        let result = fs::read(&legacy)
+            .map_err(|e| e.to_string())
+            .and_then(|data| write_avatar(user_dir, &data));
+        if let Err(e) = result {
+            log::warn!("[User] Dropping legacy avatar {:?}: {}", legacy, e);
+        }
+        let _ = fs::remove_file(&legacy);
+    }
+}
+
 /// Save an avatar image for a user
 #[tauri::command]
 pub fn save_avatar(app: AppHandle, user_id: String, source_path: String) -> Result<String, String> {
+    crate::session::require(&app, &user_id)?;
     let user_dir = get_user_dir(&app, &user_id)?;
     if !user_dir.exists() {
         return Err("User directory not found".to_string());
@@ -316,32 +1697,23 @@ pub fn save_avatar(app: AppHandle, user_id: String, source_path: String) -> Resu
         return Err("Source file does not exist".to_string());
     }
 
-    // Validate extension
-    let ext = source
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| e.to_lowercase())
-        .unwrap_or_default();
-
-    if !["jpg", "jpeg", "png", "webp", "gif", "bmp"].contains(&ext.as_str()) {
-        return Err("Unsupported image format. Use JPG, PNG, WebP, GIF or BMP.".to_string());
-    }
-
-    let avatar_filename = format!("avatar.{}", ext);
-    let avatar_path = user_dir.join(&avatar_filename);
-
-    // Remove any existing avatar with different extension
-    for existing_ext in &["jpg", "jpeg", "png", "webp", "gif", "bmp"] {
-        let old = user_dir.join(format!("avatar.{}", existing_ext));
-        if old.exists() {
-            let _ = fs::remove_file(&old);
-        }
+    let size = fs::metadata(source).map_err(|e| e.to_string())?.len();
+    if size > AVATAR_MAX_SOURCE_BYTES {
+        return Err(format!(
+            "Image is too large (max {} MB)",
+            AVATAR_MAX_SOURCE_BYTES / (1024 * 1024)
+        ));
     }
 
-    // Copy the file
-    fs::copy(source, &avatar_path).map_err(|e| e.to_string())?;
+    let data = fs::read(source).map_err(|e| e.to_string())?;
+    write_avatar(&user_dir, &data)?;
 
-    let result_path = avatar_path.to_string_lossy().to_string();
+    let largest = AVATAR_SIZES.last().copied().unwrap_or(256);
+    let result_path = user_dir
+        .join("avatar")
+        .join(format!("{}.webp", largest))
+        .to_string_lossy()
+        .to_string();
     log::info!("[User] Avatar saved for user {}: {}", user_id, result_path);
 
     Ok(result_path)
@@ -350,29 +1722,40 @@ pub fn save_avatar(app: AppHandle, user_id: String, source_path: String) -> Resu
 /// Remove the avatar for a user
 #[tauri::command]
 pub fn remove_avatar(app: AppHandle, user_id: String) -> Result<(), String> {
+    crate::session::require(&app, &user_id)?;
     let user_dir = get_user_dir(&app, &user_id)?;
 
-    for ext in &["jpg", "jpeg", "png", "webp", "gif", "bmp"] {
-        let avatar = user_dir.join(format!("avatar.{}", ext));
-        if avatar.exists() {
-            fs::remove_file(&avatar).map_err(|e| e.to_string())?;
-        }
+    let avatar_dir = user_dir.join("avatar");
+    if avatar_dir.exists() {
+        fs::remove_dir_all(&avatar_dir).map_err(|e| e.to_string())?;
     }
 
     log::info!("[User] Avatar removed for user {}", user_id);
     Ok(())
 }
 
-/// Get the avatar path for a user (returns None if no avatar)
+/// Get the avatar path for a user (returns None if no avatar).
+/// Returns the smallest generated size covering `size` pixels (largest by default).
 #[tauri::command]
-pub fn get_avatar(app: AppHandle, user_id: String) -> Result<Option<String>, String> {
+pub fn get_avatar(
+    app: AppHandle,
+    user_id: String,
+    size: Option<u32>,
+) -> Result<Option<String>, String> {
     let user_dir = get_user_dir(&app, &user_id)?;
-
-    for ext in &["jpg", "jpeg", "png", "webp", "gif", "bmp"] {
-        let avatar = user_dir.join(format!("avatar.{}", ext));
-        if avatar.exists() {
-            return Ok(Some(avatar.to_string_lossy().to_string()));
-        }
+    migrate_legacy_avatar(&user_dir);
+
+    let wanted = size.unwrap_or(u32::MAX);
+    let chosen = AVATAR_SIZES
+        .iter()
+        .find(|s| **s >= wanted)
+        .or(AVATAR_SIZES.last())
+        .copied()
+        .unwrap_or(256);
+
+    let avatar = user_dir.join("avatar").join(format!("{}.webp", chosen));
+    if avatar.exists() {
+        return Ok(Some(avatar.to_string_lossy().to_string()));
     }
 
     Ok(None)
@@ -397,3 +1780,131 @@ pub fn pick_image() -> Result<Option<String>, String> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::testing::TempDir;
+
+    fn profile() -> UserProfile {
+        UserProfile {
+            id: "user".to_string(),
+            username: "tester".to_string(),
+            created_at: chrono::Local::now().to_rfc3339(),
+            credential: CredentialPolicy::default(),
+        }
+    }
+
+    #[test]
+    fn legacy_pins_are_migrated_on_the_first_unlock() {
+        let dir = TempDir::new();
+        let legacy_key = derive_legacy_key("1234");
+        fs::write(dir.path().join("pin.hash"), legacy_key).unwrap();
+        write_profile(dir.path(), &profile(), &legacy_key).unwrap();
+
+        assert!(check_pin(dir.path(), "0000").unwrap().is_none());
+        let unlocked = check_pin(dir.path(), "1234").unwrap().unwrap();
+        assert_eq!(unlocked.profile.username, "tester");
+        assert_eq!(unlocked.replaced_key, Some(legacy_key));
+        assert_ne!(unlocked.key, legacy_key);
+        match read_pin_record(dir.path()).unwrap() {
+            StoredPin::Current(record) => assert!(!record.needs_rehash()),
+            StoredPin::Legacy(_) => panic!("pin.hash was not migrated"),
+        }
+
+        // The next unlock goes through the new record and keeps the key
+        let again = check_pin(dir.path(), "1234").unwrap().unwrap();
+        assert_eq!(again.key, unlocked.key);
+        assert_eq!(again.replaced_key, None);
+    }
+
+    #[test]
+    fn lockouts_double_after_the_free_attempts() {
+        let lockout_seconds = |state: &LockoutState| {
+            state.active_until().map(|until| {
+                let seconds = until
+                    .signed_duration_since(chrono::Local::now())
+                    .num_seconds();
+                // Rounded up, the clock moved on since the failure
+                seconds + 1
+            })
+        };
+
+        let mut state = LockoutState::default();
+        for _ in 0..LOCKOUT_FREE_ATTEMPTS {
+            state.register_failure();
+            assert_eq!(lockout_seconds(&state), None);
+        }
+        state.register_failure();
+        assert_eq!(lockout_seconds(&state), Some(LOCKOUT_BASE_SECONDS));
+        state.register_failure();
+        assert_eq!(lockout_seconds(&state), Some(LOCKOUT_BASE_SECONDS * 2));
+        state.register_failure();
+        assert_eq!(lockout_seconds(&state), Some(LOCKOUT_BASE_SECONDS * 4));
+        for _ in 0..40 {
+            state.register_failure();
+        }
+        assert_eq!(lockout_seconds(&state), Some(LOCKOUT_MAX_SECONDS));
+
+        // A locked profile refuses every PIN before checking it
+        let dir = TempDir::new();
+        state.save(dir.path()).unwrap();
+        let error = unlock_profile(dir.path(), "1234").err().unwrap();
+        assert!(error.starts_with("Too many failed PIN attempts"));
+        assert_eq!(
+            LockoutState::load(dir.path()).failed_attempts,
+            LOCKOUT_FREE_ATTEMPTS + 43
+        );
+    }
+
+    #[test]
+    fn recovery_resets_the_pin_and_keeps_the_profile_key() {
+        let dir = TempDir::new();
+        let key = generate_profile_key();
+        write_credentials(dir.path(), "1234", &profile(), &key).unwrap();
+        let code = write_recovery(dir.path(), &key).unwrap();
+        let mut lockout = LockoutState::default();
+        for _ in 0..=LOCKOUT_FREE_ATTEMPTS {
+            lockout.register_failure();
+        }
+        lockout.save(dir.path()).unwrap();
+
+        assert!(reset_pin(dir.path(), "0000-0000", "5678").is_err());
+        // Typed codes may differ in case and dashes
+        let typed = code.replace('-', " ").to_lowercase();
+        reset_pin(dir.path(), &typed, "5678").unwrap();
+
+        assert!(LockoutState::load(dir.path()).active_until().is_none());
+        assert!(check_pin(dir.path(), "1234").unwrap().is_none());
+        let unlocked = check_pin(dir.path(), "5678").unwrap().unwrap();
+        assert_eq!(unlocked.key, key);
+        assert_eq!(unlocked.profile.username, "tester");
+        // The recovery code still works afterwards
+        assert_eq!(unwrap_with_recovery(dir.path(), &code).unwrap(), Some(key));
+    }
+
+    fn bundle_header(m_cost: u32, t_cost: u32, p_cost: u32) -> BundleHeader {
+        BundleHeader {
+            version: BUNDLE_VERSION,
+            kdf: "argon2id".to_string(),
+            salt: BASE64.encode(generate_salt()),
+            m_cost,
+            t_cost,
+            p_cost,
+        }
+    }
+
+    #[test]
+    fn bundle_keys_refuse_costs_beyond_the_limit() {
+        for header in [
+            bundle_header(u32::MAX, KDF_ITERATIONS, KDF_PARALLELISM),
+            bundle_header(KDF_MEMORY_KIB, 1_000_000, KDF_PARALLELISM),
+            bundle_header(KDF_MEMORY_KIB, KDF_ITERATIONS, 64),
+        ] {
+            assert!(derive_bundle_key("passphrase", &header).is_err());
+        }
+        // Cheaper costs than ours are only bounded by Argon2 itself
+        let cheap = bundle_header(8, 1, 1);
+        assert!(derive_bundle_key("passphrase", &cheap).is_ok());
+    }
+}
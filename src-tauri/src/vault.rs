@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+use crate::media;
+
+/// Header prepended to every file written in encrypted vault mode
+const VAULT_MAGIC: &[u8] = b"PHVAULT1";
+
+/// Per-user vault configuration (stored as vault.json, unencrypted)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct VaultConfig {
+    pub enabled: bool,
+}
+
+/// In-memory vault keys of the currently unlocked users
+pub struct VaultState {
+    keys: HashMap<String, [u8; 32]>,
+}
+
+impl VaultState {
+    pub fn new() -> Self {
+        Self {
+            keys: HashMap::new(),
+        }
+    }
+}
+
+/// Derive the vault key from a profile encryption key (domain-separated so the
+/// profile key itself is never used for anything but profile.enc)
+fn derive_vault_key(profile_key: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"popcorn-hero-vault-v1");
+    hasher.update(profile_key);
+    let result = hasher.finalize();
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&result);
+    key
+}
+
+fn get_config_path(app: &AppHandle, user_id: &str) -> Result<PathBuf, String> {
+    Ok(media::get_user_dir_public(app, user_id)?.join("vault.json"))
+}
+
+/// Load the vault configuration (defaults to plaintext mode)
+pub fn load_config(app: &AppHandle, user_id: &str) -> Result<VaultConfig, String> {
+    let path = get_config_path(app, user_id)?;
+    if !path.exists() {
+        return Ok(VaultConfig::default());
+    }
+    let raw = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&raw).map_err(|e| e.to_string())
+}
+
+fn save_config(app: &AppHandle, user_id: &str, config: &VaultConfig) -> Result<(), String> {
+    let path = get_config_path(app, user_id)?;
+    let json = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    write_atomic(&path, json.as_bytes())
+}
+
+/// Replace a file through a synced temporary file, so a crash leaves either the
+/// old or the new content, never half of it
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file = fs::File::create(&tmp_path).map_err(|e| e.to_string())?;
+    file.write_all(data)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| e.to_string())?;
+    drop(file);
+    fs::rename(&tmp_path, path).map_err(|e| e.to_string())
+}
+
+/// Keep the vault key of a freshly unlocked profile in memory
+pub fn unlock(app: &AppHandle, user_id: &str, profile_key: &[u8; 32]) {
+    let state = app.state::<Arc<Mutex<VaultState>>>();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    state
+        .keys
+        .insert(user_id.to_string(), derive_vault_key(profile_key));
+}
+
+/// Forget the vault key of a user (logout, deletion), closing their library
+/// database opened with it
+pub fn lock(app: &AppHandle, user_id: &str) {
+    crate::db::close(app, user_id);
+    let state = app.state::<Arc<Mutex<VaultState>>>();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    if state.keys.remove(user_id).is_some() {
+        log::info!("[Vault] Locked vault for user {}", user_id);
+    }
+}
+
+fn current_key(app: &AppHandle, user_id: &str) -> Option<[u8; 32]> {
+    let state = app.state::<Arc<Mutex<VaultState>>>();
+    let state = state.lock().unwrap_or_else(|e| e.into_inner());
+    state.keys.get(user_id).copied()
+}
+
+/// Key of the user's library database: None in plaintext mode
+pub fn database_key(app: &AppHandle, user_id: &str) -> Result<Option<[u8; 32]>, String> {
+    if !load_config(app, user_id)?.enabled {
+        return Ok(None);
+    }
+    current_key(app, user_id)
+        .map(Some)
+        .ok_or_else(|| "Vault is locked".to_string())
+}
+
+/// Key the library database uses in the other vault mode, to finish a mode
+/// switch interrupted by a crash (None: plaintext, or the vault is locked)
+pub fn previous_database_key(app: &AppHandle, user_id: &str) -> Option<[u8; 32]> {
+    match load_config(app, user_id) {
+        Ok(config) if !config.enabled => current_key(app, user_id),
+        _ => None,
+    }
+}
+
+fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(VAULT_MAGIC)
+}
+
+fn seal(data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, String> {
+    let mut sealed = VAULT_MAGIC.to_vec();
+    sealed.extend_from_slice(&crate::user::encrypt(data, key)?);
+    Ok(sealed)
+}
+
+fn open(data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, String> {
+    crate::user::decrypt(&data[VAULT_MAGIC.len()..], key)
+        .map_err(|_| "Failed to decrypt vault file".to_string())
+}
+
+/// Read a user file, decrypting it if it was written in vault mode.
+/// Plaintext files are returned as-is, so both modes can be read at any time.
+pub fn read(app: &AppHandle, user_id: &str, path: &Path) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    if !is_sealed(&data) {
+        return Ok(data);
+    }
+    let key = current_key(app, user_id).ok_or_else(|| "Vault is locked".to_string())?;
+    open(&data, &key)
+}
+
+/// Read a user file as UTF-8 text, see `read`
+pub fn read_to_string(app: &AppHandle, user_id: &str, path: &Path) -> Result<String, String> {
+    let data = read(app, user_id, path)?;
+    String::from_utf8(data).map_err(|e| e.to_string())
+}
+
+/// Write a user file, encrypting it when the vault is enabled for this user
+pub fn write(app: &AppHandle, user_id: &str, path: &Path, data: &[u8]) -> Result<(), String> {
+    if load_config(app, user_id)?.enabled {
+        let key = current_key(app, user_id).ok_or_else(|| "Vault is locked".to_string())?;
+        write_atomic(path, &seal(data, &key)?)
+    } else {
+        write_atomic(path, data)
+    }
+}
+
+/// Every file covered by the vault: settings, scan cache, and the library files
+/// of older versions until they are imported into the database (see `db`)
+fn vault_files(user_dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![
+        user_dir.join("settings.json"),
+        user_dir.join("library.json"),
+        user_dir.join("scan_cache.json"),
+        user_dir.join("versions.json"),
+        user_dir.join("metadata_gc.json"),
+    ];
+
+    if let Ok(entries) = fs::read_dir(user_dir.join("metas")) {
+        for entry in entries.flatten() {
+            files.push(entry.path().join("meta.json"));
+        }
+    }
+
+    files.into_iter().filter(|p| p.is_file()).collect()
+}
+
+/// Switch a user's vault on or off, rewriting every covered file in the new mode.
+/// `profile_key` must come from a successful PIN unlock.
+pub fn set_enabled(
+    app: &AppHandle,
+    user_id: &str,
+    profile_key: &[u8; 32],
+    enabled: bool,
+) -> Result<usize, String> {
+    let user_dir = media::get_user_dir_public(app, user_id)?;
+    let key = derive_vault_key(profile_key);
+    let mut rewritten = 0;
+
+    // Switch the mode first: files are readable in both formats, so background
+    // writers (watcher, metadata) stay consistent while the migration runs
+    unlock(app, user_id, profile_key);
+    save_config(app, user_id, &VaultConfig { enabled })?;
+
+    for path in vault_files(&user_dir) {
+        let data = fs::read(&path).map_err(|e| e.to_string())?;
+        let sealed = is_sealed(&data);
+        if sealed == enabled {
+            continue;
+        }
+        let output = if enabled {
+            seal(&data, &key)?
+        } else {
+            open(&data, &key)?
+        };
+        write_atomic(&path, &output)?;
+        rewritten += 1;
+    }
+    if enabled {
+        crate::db::rekey(app, user_id, None, Some(&key))?;
+    } else {
+        crate::db::rekey(app, user_id, Some(&key), None)?;
+    }
+
+    log::info!(
+        "[Vault] Vault {} for user {} ({} file(s) rewritten)",
+        if enabled { "enabled" } else { "disabled" },
+        user_id,
+        rewritten
+    );
+    Ok(rewritten)
+}
+
+/// Re-encrypt every sealed file after the profile key changed (PIN update).
+/// Files already sealed with the new key are left alone, so an interrupted
+/// re-key can simply be run again.
+pub fn rekey(
+    app: &AppHandle,
+    user_id: &str,
+    old_profile_key: &[u8; 32],
+    new_profile_key: &[u8; 32],
+) -> Result<(), String> {
+    let user_dir = media::get_user_dir_public(app, user_id)?;
+    let old_key = derive_vault_key(old_profile_key);
+    let new_key = derive_vault_key(new_profile_key);
+    let mut rewritten = 0;
+
+    // Swap the in-memory key first so concurrent writers already use the new one
+    if current_key(app, user_id).is_some() {
+        unlock(app, user_id, new_profile_key);
+    }
+
+    for path in vault_files(&user_dir) {
+        let data = fs::read(&path).map_err(|e| e.to_string())?;
+        if !is_sealed(&data) {
+            continue;
+        }
+        let plain = match open(&data, &old_key) {
+            Ok(plain) => plain,
+            Err(_) if open(&data, &new_key).is_ok() => continue,
+            Err(e) => return Err(format!("{} ({})", e, path.display())),
+        };
+        write_atomic(&path, &seal(&plain, &new_key)?)?;
+        rewritten += 1;
+    }
+    if load_config(app, user_id)?.enabled {
+        crate::db::rekey(app, user_id, Some(&old_key), Some(&new_key))?;
+    }
+
+    if rewritten > 0 {
+        log::info!(
+            "[Vault] Re-keyed {} file(s) for user {}",
+            rewritten,
+            user_id
+        );
+    }
+    Ok(())
+}
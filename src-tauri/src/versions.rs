@@ -0,0 +1,392 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use tauri::AppHandle;
+
+use crate::disc::DiscFormat;
+use crate::media::{MediaEntry, MediaWithMetadata};
+
+/// Which version of a title plays when none was picked for it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionPreference {
+    /// Highest resolution, the largest file on ties
+    #[default]
+    Highest,
+    /// Lowest resolution, the smallest file on ties (slow network shares, laptops)
+    Lowest,
+}
+
+/// One file of a title available in several versions
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaVersion {
+    pub id: String,
+    pub path: String,
+    pub filename: String,
+    pub size_bytes: u64,
+    /// Display label, e.g. "4K · Remux · Director's Cut"
+    pub label: String,
+    /// "2160p", "1080p", "720p", "576p" or "480p"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    /// "Remux", "Blu-ray", "WEB-DL", "WEBRip", "HDTV" or "DVD"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// "Director's Cut", "Extended", "Unrated", ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edition: Option<String>,
+    /// The version played when the title is opened
+    pub is_default: bool,
+}
+
+/// Resolution, source and edition tags found in a filename
+struct VersionTags {
+    resolution: Option<&'static str>,
+    source: Option<&'static str>,
+    edition: Option<&'static str>,
+}
+
+fn resolution_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(?i)(?:^|[ ._\-\[(])(2160p|4k|uhd|1080[pi]|720p|576[pi]|480[pi])(?:$|[ ._\-\])])",
+        )
+        .unwrap()
+    })
+}
+
+/// Source tags, most specific first (a "BluRay Remux" is a remux)
+fn source_regexes() -> &'static [(Regex, &'static str)] {
+    static RE: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    RE.get_or_init(|| {
+        [
+            (r"remux", "Remux"),
+            (r"blu[ ._\-]?ray|bdrip|brrip|bdremux", "Blu-ray"),
+            (r"web[ ._\-]?dl", "WEB-DL"),
+            (r"web[ ._\-]?rip", "WEBRip"),
+            (r"hdtv", "HDTV"),
+            (r"dvd[ ._\-]?rip|dvd", "DVD"),
+        ]
+        .into_iter()
+        .map(|(pattern, name)| {
+            let re = format!(r"(?i)(?:^|[ ._\-\[(])(?:{})(?:$|[ ._\-\])])", pattern);
+            (Regex::new(&re).unwrap(), name)
+        })
+        .collect()
+    })
+}
+
+fn edition_regexes() -> &'static [(Regex, &'static str)] {
+    static RE: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    RE.get_or_init(|| {
+        [
+            (r"director'?s[ ._\-]?cut", "Director's Cut"),
+            (r"final[ ._\-]?cut", "Final Cut"),
+            (r"extended(?:[ ._\-]?(?:cut|edition))?", "Extended"),
+            (r"unrated", "Unrated"),
+            (r"uncut", "Uncut"),
+            (r"theatrical(?:[ ._\-]?(?:cut|edition))?", "Theatrical"),
+            (r"remastered", "Remastered"),
+            (r"imax", "IMAX"),
+            (r"criterion", "Criterion"),
+            (r"special[ ._\-]?edition", "Special Edition"),
+        ]
+        .into_iter()
+        .map(|(pattern, name)| {
+            let re = format!(r"(?i)(?:^|[ ._\-\[(])(?:{})(?:$|[ ._\-\])])", pattern);
+            (Regex::new(&re).unwrap(), name)
+        })
+        .collect()
+    })
+}
+
+fn version_tags(entry: &MediaEntry) -> VersionTags {
+    let name = &entry.filename;
+    let resolution =
+        resolution_regex()
+            .captures(name)
+            .map(|caps| match caps[1].to_ascii_lowercase().as_str() {
+                "2160p" | "4k" | "uhd" => "2160p",
+                "1080p" | "1080i" => "1080p",
+                "720p" => "720p",
+                "576p" | "576i" => "576p",
+                _ => "480p",
+            });
+    let mut source = source_regexes()
+        .iter()
+        .find(|(re, _)| re.is_match(name))
+        .map(|(_, source)| *source);
+    let edition = edition_regexes()
+        .iter()
+        .find(|(re, _)| re.is_match(name))
+        .map(|(_, edition)| *edition);
+
+    // Disc folders are named after the title, their structure tells the source
+    if let Some(ref disc) = entry.disc {
+        match disc.format {
+            DiscFormat::Bluray => source = source.or(Some("Blu-ray")),
+            DiscFormat::Dvd => source = source.or(Some("DVD")),
+            DiscFormat::Iso => {}
+        }
+    }
+
+    VersionTags {
+        resolution,
+        source,
+        edition,
+    }
+}
+
+/// Rank used to pick the default version: resolution first, then source
+fn quality_rank(tags: &VersionTags) -> u8 {
+    let resolution = match tags.resolution {
+        Some("2160p") => 4,
+        Some("1080p") => 3,
+        Some("720p") => 2,
+        Some(_) => 1,
+        // Untagged discs: a Blu-ray is at least HD, a DVD is SD
+        None => match tags.source {
+            Some("Remux") | Some("Blu-ray") => 3,
+            Some("DVD") => 1,
+            _ => 0,
+        },
+    };
+    resolution * 2 + u8::from(tags.source == Some("Remux"))
+}
+
+fn version_label(tags: &VersionTags, filename: &str) -> String {
+    let resolution = tags.resolution.map(|r| if r == "2160p" { "4K" } else { r });
+    let parts: Vec<&str> = [resolution, tags.source, tags.edition]
+        .into_iter()
+        .flatten()
+        .collect();
+    if parts.is_empty() {
+        // Nothing recognizable: the filename is the only way to tell versions apart
+        filename.to_string()
+    } else {
+        parts.join(" · ")
+    }
+}
+
+/// Key identifying the title an entry was matched to: the TMDB ID, plus the
+/// season and episode for TV. Entries without a provider match are never grouped.
+fn title_key(metadata: &serde_json::Value) -> Option<String> {
+    let tmdb_id = metadata.get("tmdb_id")?.as_u64()?;
+    match metadata.get("media_type").and_then(|t| t.as_str()) {
+        Some("movie") => Some(format!("movie:{}", tmdb_id)),
+        Some("tv") => {
+            let season = metadata.get("season_number")?.as_u64()?;
+            let episode = metadata.get("episode_number")?.as_u64()?;
+            Some(format!("tv:{}:{}:{}", tmdb_id, season, episode))
+        }
+        _ => None,
+    }
+}
+
+/// Merge the library items matched to the same title into one item per title.
+/// The item kept is the default version (picked by the user, otherwise following
+/// `preference`) and lists every version, itself included.
+pub fn group_versions(
+    app: &AppHandle,
+    user_id: &str,
+    items: Vec<MediaWithMetadata>,
+    preference: VersionPreference,
+) -> Vec<MediaWithMetadata> {
+    group(items, preference, || {
+        crate::db::load_version_pins(app, user_id).unwrap_or_default()
+    })
+}
+
+/// Grouping of `group_versions`, with the user's picks only loaded when some
+/// title has several versions
+fn group(
+    items: Vec<MediaWithMetadata>,
+    preference: VersionPreference,
+    load_pins: impl FnOnce() -> HashMap<String, String>,
+) -> Vec<MediaWithMetadata> {
+    let keys: Vec<Option<String>> = items
+        .iter()
+        .map(|item| item.metadata.as_ref().and_then(title_key))
+        .collect();
+
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        if let Some(key) = key {
+            groups.entry(key.as_str()).or_default().push(i);
+        }
+    }
+    if groups.values().all(|members| members.len() < 2) {
+        return items;
+    }
+
+    let pins = load_pins();
+    let tags: Vec<VersionTags> = items.iter().map(|item| version_tags(&item.entry)).collect();
+
+    // Default member of each group with several versions
+    let mut defaults: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (key, members) in &groups {
+        if members.len() < 2 {
+            continue;
+        }
+        let pinned = pins
+            .get(*key)
+            .and_then(|id| members.iter().find(|&&i| items[i].entry.id == *id));
+        let default = match pinned {
+            Some(&i) => i,
+            None => {
+                let quality = |&i: &usize| (quality_rank(&tags[i]), items[i].entry.size_bytes);
+                let picked = match preference {
+                    VersionPreference::Highest => members.iter().copied().max_by_key(quality),
+                    VersionPreference::Lowest => members.iter().copied().min_by_key(quality),
+                };
+                picked.unwrap_or(members[0])
+            }
+        };
+        defaults.insert(default, members.clone());
+    }
+
+    // Versions listed from the best quality down
+    let versions_of = |members: &[usize], default: usize| -> Vec<MediaVersion> {
+        let mut members = members.to_vec();
+        members.sort_by_key(|&i| {
+            std::cmp::Reverse((quality_rank(&tags[i]), items[i].entry.size_bytes))
+        });
+        members
+            .into_iter()
+            .map(|i| {
+                let entry = &items[i].entry;
+                MediaVersion {
+                    id: entry.id.clone(),
+                    path: entry.path.clone(),
+                    filename: entry.filename.clone(),
+                    size_bytes: entry.size_bytes,
+                    label: version_label(&tags[i], &entry.filename),
+                    resolution: tags[i].resolution.map(str::to_string),
+                    source: tags[i].source.map(str::to_string),
+                    edition: tags[i].edition.map(str::to_string),
+                    is_default: i == default,
+                }
+            })
+            .collect()
+    };
+    let versions: HashMap<usize, Vec<MediaVersion>> = defaults
+        .iter()
+        .map(|(&default, members)| (default, versions_of(members, default)))
+        .collect();
+
+    let grouped: HashSet<usize> = defaults.values().flatten().copied().collect();
+    let before = items.len();
+    let results: Vec<MediaWithMetadata> = items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, mut item)| {
+            if let Some(versions) = versions.get(&i) {
+                item.versions = versions.clone();
+                Some(item)
+            } else if grouped.contains(&i) {
+                None
+            } else {
+                Some(item)
+            }
+        })
+        .collect();
+
+    log::info!(
+        "[Media] Grouped {} entries into {} titles with several versions",
+        before - results.len() + defaults.len(),
+        defaults.len()
+    );
+    results
+}
+
+/// Play `media_id` by default for its title, instead of the preferred version
+#[tauri::command]
+pub fn set_default_version(
+    app: AppHandle,
+    user_id: String,
+    media_id: String,
+) -> Result<(), String> {
+    crate::session::require(&app, &user_id)?;
+    let metadata = crate::metadata::get_metadata(&app, &user_id, &media_id)?;
+    let key = title_key(&metadata)
+        .ok_or_else(|| "This file is not matched to a known title".to_string())?;
+
+    crate::db::set_version_pin(&app, &user_id, &key, &media_id)?;
+
+    log::info!("[Media] Default version of {} set to {}", key, media_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item(path: &str, size: u64, tmdb_id: Option<u64>) -> MediaWithMetadata {
+        let item = MediaWithMetadata::test(path).with_size(size);
+        match tmdb_id {
+            Some(id) => item.with_metadata(json!({"tmdb_id": id, "media_type": "movie"})),
+            None => item,
+        }
+    }
+
+    fn library() -> Vec<MediaWithMetadata> {
+        vec![
+            item("/m/Heat.1995.720p.WEB-DL.mkv", 4, Some(949)),
+            item("/m/Heat.1995.2160p.Remux.mkv", 60, Some(949)),
+            item("/m/Heat.1995.1080p.BluRay.Directors.Cut.mkv", 12, Some(949)),
+            item("/m/Alien.1979.1080p.mkv", 10, Some(348)),
+            item("/m/Unmatched.mkv", 1, None),
+            item("/m/Unmatched.Copy.mkv", 1, None),
+        ]
+    }
+
+    #[test]
+    fn versions_of_a_title_are_grouped_best_first() {
+        let grouped = group(library(), VersionPreference::Highest, HashMap::new);
+        assert_eq!(grouped.len(), 4);
+
+        let heat = grouped.iter().find(|i| !i.versions.is_empty()).unwrap();
+        assert_eq!(heat.entry.filename, "Heat.1995.2160p.Remux.mkv");
+        let labels: Vec<(&str, bool)> = heat
+            .versions
+            .iter()
+            .map(|v| (v.label.as_str(), v.is_default))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("4K · Remux", true),
+                ("1080p · Blu-ray · Director's Cut", false),
+                ("720p · WEB-DL", false),
+            ]
+        );
+        // Titles without a provider match are never grouped
+        assert!(grouped
+            .iter()
+            .filter(|i| i.entry.filename.starts_with("Unmatched"))
+            .all(|i| i.versions.is_empty()));
+    }
+
+    #[test]
+    fn default_version_follows_the_preference_then_the_pin() {
+        let lowest = group(library(), VersionPreference::Lowest, HashMap::new);
+        let heat = lowest.iter().find(|i| !i.versions.is_empty()).unwrap();
+        assert_eq!(heat.entry.filename, "Heat.1995.720p.WEB-DL.mkv");
+
+        let pins = || {
+            HashMap::from([(
+                "movie:949".to_string(),
+                "id:/m/Heat.1995.1080p.BluRay.Directors.Cut.mkv".to_string(),
+            )])
+        };
+        let pinned = group(library(), VersionPreference::Lowest, pins);
+        let heat = pinned.iter().find(|i| !i.versions.is_empty()).unwrap();
+        assert_eq!(
+            heat.entry.filename,
+            "Heat.1995.1080p.BluRay.Directors.Cut.mkv"
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use notify::event::ModifyKind;
+use notify::event::{ModifyKind, RenameMode};
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -8,7 +8,10 @@ use std::thread;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
 
-use crate::media::{self, MediaEntry, VIDEO_EXTENSIONS};
+use crate::disc::{self, DiscFormat};
+use crate::exclusions;
+use crate::media::{self, VIDEO_EXTENSIONS};
+use crate::subtitles;
 
 /// Payload emitted to the frontend when the library changes
 #[derive(serde::Serialize, Clone, Debug)]
@@ -20,6 +23,9 @@ pub struct MediaChangeEvent {
     pub total: usize,
 }
 
+/// Debounce ticks between two checks of the folders' availability (mounts)
+const AVAILABILITY_CHECK_TICKS: u32 = 5;
+
 /// Maximum number of retries when waiting for a file transfer to complete
 const FILE_STABILITY_MAX_RETRIES: u32 = 5;
 /// Delay between each file stability check
@@ -44,6 +50,14 @@ enum ChangeKind {
     Added,
     Removed,
     Modified,
+    /// A `.popcornignore` file changed: the library is rescanned
+    Rules,
+    /// A subtitle file was added, modified or removed: the tracks of the videos
+    /// it may belong to are refreshed
+    Subtitles,
+    /// A folder was renamed or moved: everything below it changed path, so the
+    /// library is rescanned
+    Folder,
 }
 
 impl WatcherState {
@@ -57,6 +71,41 @@ impl WatcherState {
     }
 }
 
+/// Pairs every path of an event with the same change kind
+fn with_kind(paths: &[PathBuf], kind: ChangeKind) -> Vec<(PathBuf, ChangeKind)> {
+    paths.iter().map(|p| (p.clone(), kind.clone())).collect()
+}
+
+/// Changes of a rename event: the old path is removed and the new one added,
+/// and the metadata follows the file through its fingerprint. A renamed folder
+/// moves everything below it, so it is reported as a whole.
+fn rename_changes(paths: &[PathBuf], mode: RenameMode) -> Vec<(PathBuf, ChangeKind)> {
+    let added = |path: &PathBuf| {
+        if path.is_dir() {
+            (path.clone(), ChangeKind::Folder)
+        } else {
+            (path.clone(), ChangeKind::Added)
+        }
+    };
+    match (mode, paths) {
+        (RenameMode::From, _) => with_kind(paths, ChangeKind::Removed),
+        (RenameMode::To, _) => paths.iter().map(added).collect(),
+        (RenameMode::Both, [from, to]) => vec![(from.clone(), ChangeKind::Removed), added(to)],
+        // The backend did not tell which side this is (e.g. FSEvents): the
+        // path is the new name if it still exists
+        _ => paths
+            .iter()
+            .map(|p| {
+                if p.exists() {
+                    added(p)
+                } else {
+                    (p.clone(), ChangeKind::Removed)
+                }
+            })
+            .collect(),
+    }
+}
+
 /// Check if a path has a media file extension
 fn has_media_extension(path: &Path) -> bool {
     path.extension()
@@ -65,30 +114,72 @@ fn has_media_extension(path: &Path) -> bool {
         .unwrap_or(false)
 }
 
-/// Create a MediaEntry from a file path
-fn entry_from_path(path: &Path) -> Option<MediaEntry> {
-    if !path.is_file() {
-        return None;
+/// Check if a path is a `.popcornignore` file
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == exclusions::IGNORE_FILE_NAME)
+}
+
+/// Rescan the whole library and notify the frontend
+fn rescan_library(app: &AppHandle, user_id: &str) {
+    match media::scan_library(app, user_id) {
+        Ok(result) => {
+            let _ = app.emit(
+                "media-change",
+                MediaChangeEvent {
+                    kind: "full_scan".to_string(),
+                    added: vec![],
+                    modified: vec![],
+                    removed: vec![],
+                    total: result.total_found,
+                },
+            );
+        }
+        Err(e) => log::warn!("[Watcher] Rescan failed: {}", e),
     }
-    let ext = path.extension()?.to_str()?.to_lowercase();
-    if !VIDEO_EXTENSIONS.contains(&ext.as_str()) {
-        return None;
+}
+
+/// Library folders that cannot be reached (unmounted drive or share). The
+/// library is only loaded for empty folders, to tell them from a mount point.
+fn offline_folders(app: &AppHandle, user_id: &str, config: &media::MediaConfig) -> HashSet<String> {
+    let mut library: Option<Vec<media::MediaEntry>> = None;
+    config
+        .folders
+        .iter()
+        .filter(|folder| {
+            let empty = match std::fs::read_dir(&folder.path) {
+                Ok(mut listing) => listing.next().is_none(),
+                Err(_) => return true,
+            };
+            empty && {
+                let entries = library
+                    .get_or_insert_with(|| media::load_library(app, user_id).unwrap_or_default());
+                !media::is_folder_available(config, folder, entries.iter())
+            }
+        })
+        .map(|folder| folder.path.clone())
+        .collect()
+}
+
+/// Watch a folder again once it is back online (its watch ended with the unmount).
+/// Returns false when the watcher is busy, to be retried on the next check.
+fn rewatch_folder(app: &AppHandle, user_id: &str, path: &Path) -> bool {
+    let state = app.state::<Arc<Mutex<WatcherState>>>();
+    // stop_watching joins the debounce thread while holding the lock: never wait for it
+    let mut state = match state.try_lock() {
+        Ok(state) => state,
+        Err(_) => {
+            log::debug!("[Watcher] Watcher busy, watching {:?} again later", path);
+            return false;
+        }
+    };
+    if let Some(watcher) = state.watchers.get_mut(user_id) {
+        match watcher.watch(path, RecursiveMode::Recursive) {
+            Ok(()) => log::info!("[Watcher] Watching folder again: {}", path.display()),
+            Err(e) => log::warn!("[Watcher] Failed to watch folder {}: {}", path.display(), e),
+        }
     }
-    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-    let filename = path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("unknown")
-        .to_string();
-    let file_path = path.to_string_lossy().replace('\\', "/");
-
-    Some(MediaEntry {
-        id: uuid::Uuid::new_v4().to_string(),
-        path: file_path,
-        filename,
-        extension: ext,
-        size_bytes: size,
-    })
+    true
 }
 
 /// Wait for a file to finish being transferred/copied.
@@ -152,8 +243,8 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
     state.stop_flag = stop_flag.clone();
 
     // Load config
-    let config = media::get_media_config(app.clone(), user_id.to_string())?;
-    let settings = media::get_settings(app.clone(), user_id.to_string())?;
+    let settings = media::load_settings(app, user_id)?;
+    let config = settings.media.clone();
 
     if config.folders.is_empty() {
         log::info!("[Watcher] No folders to watch for user {}", user_id);
@@ -167,7 +258,7 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
             config.folders.len(),
             user_id
         );
-        let scan_result = media::scan_media_folders(app.clone(), user_id.to_string())?;
+        let scan_result = media::scan_library(app, user_id)?;
         let _ = app.emit(
             "media-change",
             MediaChangeEvent {
@@ -200,6 +291,13 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
     let debounce_thread = thread::spawn(move || {
         log::info!("[Watcher] Debounce processor thread started");
 
+        let mut offline = media::load_settings(&app_for_thread, &user_for_thread)
+            .map(|s| offline_folders(&app_for_thread, &user_for_thread, &s.media))
+            .unwrap_or_default();
+        // Folders back online whose watch could not be restored yet
+        let mut unwatched: HashSet<String> = HashSet::new();
+        let mut ticks: u32 = 0;
+
         while !stop_for_thread.load(Ordering::SeqCst) {
             // Sleep for 2 seconds (debounce interval)
             thread::sleep(Duration::from_secs(2));
@@ -208,6 +306,34 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                 break;
             }
 
+            // Notice drives and shares being mounted or unmounted: their entries
+            // are marked offline, or reconciled, by a rescan
+            ticks += 1;
+            if ticks.is_multiple_of(AVAILABILITY_CHECK_TICKS) {
+                if let Ok(settings) = media::load_settings(&app_for_thread, &user_for_thread) {
+                    let now_offline =
+                        offline_folders(&app_for_thread, &user_for_thread, &settings.media);
+                    unwatched.retain(|path| {
+                        !now_offline.contains(path)
+                            && !rewatch_folder(&app_for_thread, &user_for_thread, Path::new(path))
+                    });
+                    if now_offline != offline {
+                        for path in now_offline.difference(&offline) {
+                            log::info!("[Watcher] Folder went offline: {}", path);
+                        }
+                        for path in offline.difference(&now_offline) {
+                            log::info!("[Watcher] Folder back online: {}", path);
+                            if !rewatch_folder(&app_for_thread, &user_for_thread, Path::new(path)) {
+                                unwatched.insert(path.clone());
+                            }
+                        }
+                        offline = now_offline;
+                        rescan_library(&app_for_thread, &user_for_thread);
+                        continue;
+                    }
+                }
+            }
+
             // Drain pending changes
             let changes: Vec<PendingChange> = {
                 let mut pending = match pending_for_thread.lock() {
@@ -274,23 +400,75 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                 changes.len()
             );
 
+            // Changed ignore rules or moved folders affect whole subtrees
+            if changes
+                .iter()
+                .any(|c| matches!(c.kind, ChangeKind::Rules | ChangeKind::Folder))
+            {
+                log::info!("[Watcher] Ignore rules or folders changed, rescanning library");
+                rescan_library(&app_for_thread, &user_for_thread);
+                continue;
+            }
+
+            let config = match media::load_settings(&app_for_thread, &user_for_thread) {
+                Ok(settings) => settings.media,
+                Err(e) => {
+                    log::warn!("[Watcher] Failed to load settings: {}", e);
+                    continue;
+                }
+            };
+
             // Load current library
-            let current_entries =
-                match media::get_media_library(app_for_thread.clone(), user_for_thread.clone()) {
-                    Ok(entries) => entries,
-                    Err(e) => {
-                        log::warn!("[Watcher] Failed to load library: {}", e);
-                        continue;
-                    }
-                };
+            let current_entries = match media::load_library(&app_for_thread, &user_for_thread) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("[Watcher] Failed to load library: {}", e);
+                    continue;
+                }
+            };
+
+            // Removals on an unmounted folder are not deletions
+            let offline_roots: HashSet<&str> = config
+                .folders
+                .iter()
+                .filter(|f| !media::is_folder_available(&config, f, &current_entries))
+                .map(|f| f.path.as_str())
+                .collect();
+
+            // Multi-part titles are regrouped by a (cached, cheap) rescan
+            let stacked = changes.iter().any(|c| {
+                let path = c.path.to_string_lossy().replace('\\', "/");
+                media::is_stack_part(&c.path)
+                    || current_entries.iter().any(|e| e.parts.contains(&path))
+            });
+            if stacked {
+                log::info!("[Watcher] Multi-part title changed, rescanning library");
+                rescan_library(&app_for_thread, &user_for_thread);
+                continue;
+            }
 
-            let mut entries = current_entries;
+            let mut entries = current_entries.clone();
             let mut changed = false;
             let mut added_files: Vec<String> = Vec::new();
             let mut modified_files: Vec<String> = Vec::new();
             let mut removed_files: Vec<String> = Vec::new();
+            // Paths added or modified in this batch, with the ID they had before
+            let mut touched: Vec<(String, Option<String>)> = Vec::new();
+            // Blu-ray/DVD folders whose streams changed, rebuilt as a whole below
+            let mut disc_roots: HashSet<PathBuf> = HashSet::new();
+            // Folders whose videos may have gained or lost a subtitle track
+            let mut subtitle_dirs: HashSet<PathBuf> = HashSet::new();
 
             for change in &changes {
+                if matches!(change.kind, ChangeKind::Subtitles) {
+                    subtitle_dirs.extend(subtitles::video_dirs_of(&change.path));
+                    continue;
+                }
+                if let Some(root) = disc::disc_root_of(&change.path) {
+                    disc_roots.insert(root);
+                    continue;
+                }
+
                 let normalized_path = change.path.to_string_lossy().replace('\\', "/");
                 let filename = change
                     .path
@@ -299,6 +477,13 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                     .unwrap_or("unknown")
                     .to_string();
 
+                if !matches!(change.kind, ChangeKind::Removed)
+                    && exclusions::is_path_ignored(&config, &change.path, false)
+                {
+                    log::debug!("[Watcher] Skipping excluded file: {}", normalized_path);
+                    continue;
+                }
+
                 match change.kind {
                     ChangeKind::Added => {
                         // Wait for file transfer to complete before processing
@@ -309,9 +494,14 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                             );
                             continue;
                         }
-                        if let Some(entry) = entry_from_path(&change.path) {
+                        if let Some(entry) = media::entry_from_file(&change.path) {
                             // Only count as "added" if it wasn't already in the library
-                            let was_present = entries.iter().any(|e| e.path == normalized_path);
+                            let previous_id = entries
+                                .iter()
+                                .find(|e| e.path == normalized_path)
+                                .map(|e| e.id.clone());
+                            let was_present = previous_id.is_some();
+                            touched.push((normalized_path.clone(), previous_id));
                             entries.retain(|e| e.path != normalized_path);
                             log::info!("[Watcher] Media file added: {}", entry.path);
                             entries.push(entry);
@@ -330,7 +520,12 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                             );
                             continue;
                         }
-                        if let Some(entry) = entry_from_path(&change.path) {
+                        if let Some(entry) = media::entry_from_file(&change.path) {
+                            let previous_id = entries
+                                .iter()
+                                .find(|e| e.path == normalized_path)
+                                .map(|e| e.id.clone());
+                            touched.push((normalized_path.clone(), previous_id));
                             entries.retain(|e| e.path != normalized_path);
                             log::info!("[Watcher] Media file modified: {}", entry.path);
                             entries.push(entry);
@@ -339,6 +534,16 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                         }
                     }
                     ChangeKind::Removed => {
+                        let offline_root = config
+                            .folder_for(&change.path)
+                            .is_some_and(|f| offline_roots.contains(f.path.as_str()));
+                        if offline_root {
+                            log::debug!(
+                                "[Watcher] Ignoring removal on an offline folder: {}",
+                                normalized_path
+                            );
+                            continue;
+                        }
                         let before = entries.len();
                         entries.retain(|e| e.path != normalized_path);
                         if entries.len() != before {
@@ -347,6 +552,70 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                             removed_files.push(filename);
                         }
                     }
+                    ChangeKind::Rules | ChangeKind::Subtitles | ChangeKind::Folder => {}
+                }
+            }
+
+            for root in &disc_roots {
+                let normalized_path = root.to_string_lossy().replace('\\', "/");
+                if exclusions::is_path_ignored(&config, root, true) {
+                    continue;
+                }
+                let filename = root
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string();
+                let previous_id = entries
+                    .iter()
+                    .find(|e| e.path == normalized_path)
+                    .map(|e| e.id.clone());
+                entries.retain(|e| e.path != normalized_path);
+
+                match media::entry_from_disc(root) {
+                    Some(entry) => {
+                        log::info!("[Watcher] Disc folder updated: {}", entry.path);
+                        if previous_id.is_some() {
+                            modified_files.push(filename);
+                        } else {
+                            added_files.push(filename);
+                        }
+                        touched.push((normalized_path, previous_id));
+                        entries.push(entry);
+                        changed = true;
+                    }
+                    None if previous_id.is_some() => {
+                        log::info!("[Watcher] Disc folder removed: {}", normalized_path);
+                        removed_files.push(filename);
+                        changed = true;
+                    }
+                    None => {}
+                }
+            }
+
+            // Refresh the tracks of the videos next to changed subtitle files
+            if !subtitle_dirs.is_empty() {
+                for entry in entries.iter_mut() {
+                    let is_disc_folder = entry
+                        .disc
+                        .as_ref()
+                        .is_some_and(|d| d.format != DiscFormat::Iso);
+                    let path = Path::new(&entry.path);
+                    let in_dir = path.parent().is_some_and(|d| subtitle_dirs.contains(d));
+                    if is_disc_folder || !in_dir {
+                        continue;
+                    }
+                    let tracks = subtitles::discover(path);
+                    if tracks != entry.subtitles {
+                        log::info!(
+                            "[Watcher] Subtitles of {} updated: {} track(s)",
+                            entry.path,
+                            tracks.len()
+                        );
+                        entry.subtitles = tracks;
+                        modified_files.push(entry.filename.clone());
+                        changed = true;
+                    }
                 }
             }
 
@@ -354,30 +623,31 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
                 // Sort and dedup
                 entries.sort_by(|a, b| a.path.cmp(&b.path));
                 entries.dedup_by(|a, b| a.path == b.path);
+                media::assign_unique_ids(&mut entries);
+
+                // A moved or rewritten file keeps its metadata
+                for (path, previous_id) in &touched {
+                    if let Some(entry) = entries.iter().find(|e| &e.path == path) {
+                        if let Err(e) = crate::metadata::reattach_metadata(
+                            &app_for_thread,
+                            &user_for_thread,
+                            previous_id.as_deref(),
+                            entry,
+                        ) {
+                            log::warn!("[Watcher] Failed to reattach metadata for {}: {}", path, e);
+                        }
+                    }
+                }
 
-                // Save library
-                let library_path = match media::get_library_path_public(
+                // Save the changes of this batch only
+                if let Err(e) = media::save_library(
                     &app_for_thread,
                     &user_for_thread,
+                    &current_entries,
+                    &entries,
                 ) {
-                    Ok(p) => p,
-                    Err(e) => {
-                        log::warn!("[Watcher] Failed to get library path: {}", e);
-                        continue;
-                    }
-                };
-
-                match serde_json::to_string_pretty(&entries) {
-                    Ok(json) => {
-                        if let Err(e) = std::fs::write(&library_path, json) {
-                            log::warn!("[Watcher] Failed to write library: {}", e);
-                            continue;
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("[Watcher] Failed to serialize library: {}", e);
-                        continue;
-                    }
+                    log::warn!("[Watcher] Failed to write library: {}", e);
+                    continue;
                 }
 
                 let total = entries.len();
@@ -423,25 +693,32 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
     let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
         match res {
             Ok(event) => {
-                let change_kind = match event.kind {
-                    EventKind::Create(_) => Some(ChangeKind::Added),
-                    EventKind::Modify(ModifyKind::Data(_))
-                    | EventKind::Modify(ModifyKind::Any) => Some(ChangeKind::Modified),
-                    EventKind::Remove(_) => Some(ChangeKind::Removed),
-                    _ => None,
+                let changes: Vec<(PathBuf, ChangeKind)> = match event.kind {
+                    EventKind::Create(_) => with_kind(&event.paths, ChangeKind::Added),
+                    EventKind::Modify(ModifyKind::Name(mode)) => rename_changes(&event.paths, mode),
+                    EventKind::Modify(ModifyKind::Data(_)) | EventKind::Modify(ModifyKind::Any) => {
+                        with_kind(&event.paths, ChangeKind::Modified)
+                    }
+                    EventKind::Remove(_) => with_kind(&event.paths, ChangeKind::Removed),
+                    _ => Vec::new(),
                 };
 
-                if let Some(kind) = change_kind {
-                    for path in &event.paths {
-                        if has_media_extension(path) {
-                            let mut pending =
-                                pending_for_watcher.lock().unwrap_or_else(|e| e.into_inner());
-                            pending.push(PendingChange {
-                                path: path.clone(),
-                                kind: kind.clone(),
-                            });
-                        }
-                    }
+                for (path, kind) in changes {
+                    let kind = if matches!(kind, ChangeKind::Folder) {
+                        kind
+                    } else if is_ignore_file(&path) {
+                        ChangeKind::Rules
+                    } else if subtitles::is_subtitle(&path) {
+                        ChangeKind::Subtitles
+                    } else if has_media_extension(&path) {
+                        kind
+                    } else {
+                        continue;
+                    };
+                    let mut pending = pending_for_watcher
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner());
+                    pending.push(PendingChange { path, kind });
                 }
             }
             Err(e) => {
@@ -453,14 +730,14 @@ pub fn start_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
 
     // Watch each configured folder
     for folder in &config.folders {
-        let path = Path::new(folder);
+        let path = Path::new(&folder.path);
         if path.is_dir() {
             match watcher.watch(path, RecursiveMode::Recursive) {
                 Ok(()) => {
-                    log::info!("[Watcher] Watching folder: {}", folder);
+                    log::info!("[Watcher] Watching folder: {}", folder.path);
                 }
                 Err(e) => {
-                    log::warn!("[Watcher] Failed to watch folder {}: {}", folder, e);
+                    log::warn!("[Watcher] Failed to watch folder {}: {}", folder.path, e);
                 }
             }
         }
@@ -499,7 +776,7 @@ pub fn stop_watching(app: &AppHandle, user_id: &str) -> Result<(), String> {
     }
 
     // Stop the metadata retry thread
-    if let Err(e) = crate::metadata::stop_metadata_retry(app) {
+    if let Err(e) = crate::metadata::stop_metadata_retry(app, user_id) {
         log::warn!("[Watcher] Failed to stop metadata retry thread: {}", e);
     }
 
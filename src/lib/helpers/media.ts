@@ -1,5 +1,6 @@
 import { convertFileSrc } from "@tauri-apps/api/core";
-import type { MediaWithMetadata } from "$lib/types/media";
+import { m } from "$lib/paraglide/messages.js";
+import type { ExtraKind, MediaWithMetadata } from "$lib/types/media";
 
 /** Convert a local file path to a WebView-loadable URL */
 export function toAssetUrl(filePath: string | null | undefined): string | null {
@@ -10,7 +11,7 @@ export function toAssetUrl(filePath: string | null | undefined): string | null {
 /** Check if a media entry is a TV series based on metadata media_type */
 export function isTvShow(item: MediaWithMetadata): boolean {
 	if (item.metadata?.media_type === "tv") return true;
-	if (item.metadata?.media_type === "movie") return false;
+	if (["movie", "home_video", "extra"].includes(item.metadata?.media_type ?? "")) return false;
 	// Fallback for old metadata without media_type field
 	if (item.metadata?.season_number != null || item.metadata?.episode_number != null) return true;
 	// Fallback: check filename for TV patterns (S01E02, etc.)
@@ -26,9 +27,36 @@ export function isMovie(item: MediaWithMetadata): boolean {
 	return false;
 }
 
+/** Check if a media entry comes from a home videos folder */
+export function isHomeVideo(item: MediaWithMetadata): boolean {
+	return item.metadata?.media_type === "home_video";
+}
+
 /** Check if a media entry is unidentified (no API metadata, local-only, or unknown type) */
 export function isUnidentified(item: MediaWithMetadata): boolean {
-	return !isMovie(item) && !isTvShow(item);
+	return !isMovie(item) && !isTvShow(item) && !isHomeVideo(item);
+}
+
+/** Get the localized name of a kind of extra */
+export function getExtraKindLabel(kind: ExtraKind): string {
+	switch (kind) {
+		case "trailer":
+			return m.media_extra_trailer();
+		case "featurette":
+			return m.media_extra_featurette();
+		case "behind_the_scenes":
+			return m.media_extra_behind_the_scenes();
+		case "deleted_scene":
+			return m.media_extra_deleted_scene();
+		case "interview":
+			return m.media_extra_interview();
+		case "scene":
+			return m.media_extra_scene();
+		case "short":
+			return m.media_extra_short();
+		default:
+			return m.media_extra_other();
+	}
 }
 
 /** Get display title for a media entry */
@@ -92,14 +120,7 @@ export function getBestRated(items: MediaWithMetadata[], count = 5): MediaWithMe
 		.slice(0, count);
 }
 
-/** Get recently added items */
+/** Get recently added items, by the date their file was first found */
 export function getRecentlyAdded(items: MediaWithMetadata[], count = 20): MediaWithMetadata[] {
-	return [...items]
-		.filter((i) => i.metadata?.fetched_at)
-		.sort((a, b) => {
-			const da = a.metadata?.fetched_at ?? "";
-			const db = b.metadata?.fetched_at ?? "";
-			return db.localeCompare(da);
-		})
-		.slice(0, count);
+	return [...items].sort((a, b) => b.added_at - a.added_at).slice(0, count);
 }
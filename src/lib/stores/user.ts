@@ -1,9 +1,16 @@
 import { writable } from "svelte/store";
 
+/** How a profile is unlocked (mirrors `CredentialPolicy` in the backend) */
+export type CredentialPolicy =
+	| { kind: "pin"; digits: number }
+	| { kind: "passphrase" }
+	| { kind: "none" };
+
 export interface UserProfile {
 	id: string;
 	username: string;
 	created_at: string;
+	credential: CredentialPolicy;
 }
 
 function createPersistentUser() {
@@ -5,10 +5,84 @@ export interface MediaWithMetadata {
 	filename: string;
 	extension: string;
 	size_bytes: number;
+	/** Set when the entry is a Blu-ray/DVD folder or a disc image */
+	disc?: DiscInfo;
+	/** Files of a multi-part title (CD1, CD2...) in playback order */
+	parts?: string[];
+	/** External subtitle files found next to the video */
+	subtitles?: SubtitleTrack[];
+	/** False while the drive or share holding the file is not connected */
+	available?: boolean;
+	/** Unix time the file was first found, kept across rescans, renames and moves */
+	added_at: number;
+	/** Unix time the file was last found by a scan or the watcher */
+	last_seen_at: number;
+	/** File modification time, Unix seconds */
+	modified_at?: number;
+	/** File creation time (inode change time where unknown), Unix seconds */
+	created_at?: number;
 	metadata: VideoMetadata | null;
 	poster_path: string | null;
 	backdrop_path: string | null;
 	still_path: string | null;
+	/** Every file of the title when it exists in several versions, this one included */
+	versions?: MediaVersion[];
+	/** Trailers, featurettes and other extras found with the title */
+	extras?: MediaExtra[];
+}
+
+export interface MediaVersion {
+	id: string;
+	path: string;
+	filename: string;
+	size_bytes: number;
+	/** Display label, e.g. "4K · Remux · Director's Cut" */
+	label: string;
+	resolution?: string;
+	source?: string;
+	edition?: string;
+	/** The version played when the title is opened */
+	is_default: boolean;
+}
+
+export type ExtraKind =
+	| "trailer"
+	| "featurette"
+	| "behind_the_scenes"
+	| "deleted_scene"
+	| "interview"
+	| "scene"
+	| "short"
+	| "extra";
+
+export interface MediaExtra {
+	id: string;
+	path: string;
+	filename: string;
+	size_bytes: number;
+	kind: ExtraKind;
+	/** Name of the extra, without its kind suffix */
+	title: string;
+}
+
+export interface SubtitleTrack {
+	path: string;
+	/** File extension: srt, ass, ssa, vtt, sub or idx */
+	format: string;
+	/** ISO 639-1 code ("fr", "pt-BR"), or the tag as written when unknown */
+	language?: string;
+	/** Only covers foreign-language parts of the audio */
+	forced: boolean;
+	/** Subtitles for the deaf and hard of hearing */
+	sdh: boolean;
+	/** Remaining tags of the name ("Commentary") */
+	title?: string;
+}
+
+export interface DiscInfo {
+	format: "bluray" | "dvd" | "iso";
+	/** Files of the main feature, in playback order */
+	main_feature: string[];
 }
 
 export interface VideoMetadata {
@@ -40,7 +114,7 @@ export interface VideoMetadata {
 	file_path?: string;
 	provider: string;
 	fetched_at: string;
-	/** Media type: "movie", "tv", or "unknown" */
+	/** Media type: "movie", "tv", "home_video", "extra" (no parent title found), or "unknown" */
 	media_type?: string;
 }
 
@@ -60,3 +134,53 @@ export interface MetadataImages {
 	poster?: string;
 	backdrop?: string;
 }
+
+/** Filters of `query_library`: empty lists and unset bounds match everything */
+export interface LibraryFilter {
+	/** "movie", "tv", "home_video", "extra" or "unknown" (no metadata) */
+	media_types?: string[];
+	genres?: string[];
+	year_min?: number;
+	year_max?: number;
+	rating_min?: number;
+	rating_max?: number;
+	/** File extensions ("mkv", "iso"...) */
+	containers?: string[];
+	providers?: string[];
+	available?: boolean;
+	/** Words starting words of the titles, overview, cast, crew or studios (accents aside), or found in the filename */
+	text?: string;
+}
+
+/** "relevance": best matches of the text filter first */
+export type LibrarySort = "title" | "year" | "rating" | "date_added" | "size" | "relevance";
+
+export interface LibraryQuery {
+	filter?: LibraryFilter;
+	sort?: LibrarySort;
+	descending?: boolean;
+	/** `next_cursor` of the previous page */
+	cursor?: string | null;
+	limit?: number;
+}
+
+export interface LibraryPage {
+	items: MediaWithMetadata[];
+	/** Titles matching the filter, over all pages */
+	total: number;
+	/** Cursor of the next page, null on the last one */
+	next_cursor: string | null;
+}
+
+/** An entry of `get_recently_added`: a title, or the recent episodes of a series */
+export type RecentTitle =
+	| { kind: "media"; item: MediaWithMetadata }
+	| {
+			kind: "series";
+			title: string;
+			/** When its newest episode was added */
+			added_at: number;
+			poster_path: string | null;
+			/** Newest first */
+			episodes: MediaWithMetadata[];
+	  };